@@ -18,6 +18,12 @@ interface Point {
 
 type Direction = 'Up' | 'Down' | 'Left' | 'Right';
 
+interface Death {
+    cause: string;
+    killer: string | null;
+    tick: number;
+}
+
 interface Snake {
     body: Point[];
     direction: Direction;
@@ -25,6 +31,7 @@ interface Snake {
     alive: boolean;
     score: number;
     color: string;
+    death?: Death | null;
 }
 
 interface Player {
@@ -33,20 +40,13 @@ interface Player {
     snake: Snake;
     ready: boolean;
     active_power: ActivePowerUp | null;
+    total_score: number;
+    kills: number;
+    deaths: number;
+    skill?: number;
 }
 
-type GamePhase = 'Lobby' | 'Countdown' | 'Playing' | 'GameOver';
-
-interface GameState {
-    phase: GamePhase;
-    players: Record<string, Player>;
-    food: Point[];
-    grid_width: number;
-    grid_height: number;
-    winner: string | null;
-    countdown: number;
-    power_ups: PowerUp[];
-}
+type GamePhase = 'Lobby' | 'Countdown' | 'Playing' | 'RoundOver' | 'MatchOver';
 
 type PowerUpType = 'SpeedBoost' | 'Shield' | 'Grow' | 'Ghost';
 
@@ -80,16 +80,122 @@ interface GameState {
     winner: string | null;
     countdown: number;
     power_ups: PowerUp[];
+    round: number;
+    total_rounds: number;
+    tick: number;
+    spectators: string[]; // Display names, sorted
+    mode: 'Classic' | 'Arena';
     room_code?: string;
 }
+
+// Change to one snake since the previous tick (see WEBSOCKET.md)
+interface SnakeDelta {
+    player_id: string;
+    new_cells: Point[];
+    length: number;
+    growing?: number;
+    direction: Direction;
+    score: number;
+    total_score: number;
+    kills: number;
+    deaths: number;
+    active_power: PowerUpType | null;
+    death?: Death;
+    skill?: number;
+}
+
+interface GameDelta {
+    tick: number;
+    snakes: SnakeDelta[];
+    deaths: string[];
+    food_added: Point[];
+    food_removed: Point[];
+    power_ups_added: PowerUp[];
+    power_ups_removed: string[];
+}
+
+interface Standing {
+    player_id: string;
+    name: string;
+    round_wins: number;
+    total_score: number;
+    kills: number;
+    deaths: number;
+}
+
 type ServerMessage =
-    | { type: 'Welcome'; payload: { player_id: string } }
+    | { type: 'Welcome'; payload: { player_id: string; resume_token: string } }
     | { type: 'GameState'; payload: GameState }
+    | { type: 'Delta'; payload: GameDelta }
     | { type: 'PlayerJoined'; payload: { player_id: string; name: string } }
     | { type: 'PlayerLeft'; payload: { player_id: string } }
     | { type: 'Error'; payload: { message: string } }
+    | { type: 'AuthFailed'; payload: { code: string; message: string } }
     | { type: 'GameStarted' }
-    | { type: 'GameOver'; payload: { winner: string | null } };
+    | { type: 'RoundOver'; payload: { round: number; winner: string | null; standings: Standing[] } }
+    | { type: 'MatchOver'; payload: { winner: string | null; standings: Standing[]; replay_id: string | null } };
+
+// =============================================================================
+// DELTAS
+// =============================================================================
+
+const samePoint = (a: Point, b: Point) => a.x === b.x && a.y === b.y;
+
+// Prepend the new cells, trim to `length - growing`, then pad to `length` with the tail
+function applySnakeDelta(body: Point[], delta: SnakeDelta): Point[] {
+    const next = [...delta.new_cells, ...body].slice(0, Math.max(delta.length - (delta.growing ?? 0), 1));
+    while (next.length > 0 && next.length < delta.length) {
+        next.push(next[next.length - 1]);
+    }
+    return next;
+}
+
+// The state after `delta`, or null if it doesn't follow on from `state` (ask for a Resync)
+function applyDelta(state: GameState, delta: GameDelta): GameState | null {
+    if (delta.tick !== state.tick + 1) return null;
+
+    const players = { ...state.players };
+    for (const change of delta.snakes) {
+        const player = players[change.player_id];
+        if (!player) return null;
+        const keepsPower = player.active_power && player.active_power.power_type === change.active_power;
+        players[change.player_id] = {
+            ...player,
+            total_score: change.total_score,
+            kills: change.kills,
+            deaths: change.deaths,
+            skill: change.skill,
+            active_power: change.active_power
+                ? (keepsPower ? player.active_power : { power_type: change.active_power, ticks_remaining: 0 })
+                : null,
+            snake: {
+                ...player.snake,
+                body: applySnakeDelta(player.snake.body, change),
+                direction: change.direction,
+                score: change.score,
+                death: change.death ?? null,
+            },
+        };
+    }
+    for (const id of delta.deaths) {
+        const player = players[id];
+        if (player) players[id] = { ...player, snake: { ...player.snake, alive: false } };
+    }
+
+    return {
+        ...state,
+        tick: delta.tick,
+        players,
+        food: [
+            ...state.food.filter(f => !delta.food_removed.some(r => samePoint(r, f))),
+            ...delta.food_added,
+        ],
+        power_ups: [
+            ...state.power_ups.filter(pu => !delta.power_ups_removed.includes(pu.id)),
+            ...delta.power_ups_added,
+        ],
+    };
+}
 
 // =============================================================================
 // CONSTANTS
@@ -351,7 +457,12 @@ function Lobby({
         <div className="flex flex-col items-center justify-center h-full gap-8 p-8">
             <div className="text-center">
                 <h2 className="text-3xl font-bold text-white mb-2">Waiting for Players</h2>
-                <p className="text-slate-400">Up to 4 players can join this battle</p>
+                <p className="text-slate-400">
+                    Up to {gameState.mode === 'Arena' ? 64 : 4} players can join this battle
+                </p>
+                {gameState.spectators.length > 0 && (
+                    <p className="text-slate-500 text-sm mt-1">Watching: {gameState.spectators.join(', ')}</p>
+                )}
                 {roomCode && (
                     <div className="mt-4 bg-white/5 border border-white/10 rounded-lg px-4 py-2 inline-block">
                         <span className="text-slate-400 text-sm mr-2">ROOM CODE:</span>
@@ -513,7 +624,27 @@ function PowerUpInfo() {
 // GAME OVER OVERLAY
 // =============================================================================
 
-function GameOverOverlay({ winner, onRestart, onPlayAgain }: { winner: string | null; onRestart: () => void; onPlayAgain: () => void }) {
+function RoundOverOverlay({ round, totalRounds, winner, countdown }: { round: number; totalRounds: number; winner: string | null; countdown: number }) {
+    return (
+        <motion.div
+            initial={{ opacity: 0 }}
+            animate={{ opacity: 1 }}
+            className="absolute inset-0 bg-black/60 backdrop-blur-sm flex items-center justify-center z-20 rounded-xl"
+        >
+            <div className="text-center space-y-2">
+                <h2 className="text-3xl font-bold text-white">Round {round} of {totalRounds}</h2>
+                {winner ? (
+                    <p className="text-lg text-violet-300">{winner} takes the round</p>
+                ) : (
+                    <p className="text-lg text-slate-400">No one survived</p>
+                )}
+                <p className="text-sm text-slate-400">Next round in {countdown}...</p>
+            </div>
+        </motion.div>
+    );
+}
+
+function GameOverOverlay({ winner, standings, onRestart, onPlayAgain }: { winner: string | null; standings: Standing[]; onRestart: () => void; onPlayAgain: () => void }) {
     return (
         <motion.div
             initial={{ opacity: 0 }}
@@ -530,6 +661,16 @@ function GameOverOverlay({ winner, onRestart, onPlayAgain }: { winner: string |
                         <p className="text-xl text-slate-400">No survivors...</p>
                     )}
                 </div>
+                {standings.length > 0 && (
+                    <div className="space-y-1 text-sm">
+                        {standings.map((row, idx) => (
+                            <div key={row.player_id} className="flex justify-between gap-6 text-slate-300">
+                                <span>{idx + 1}. {row.name}</span>
+                                <span className="font-mono">{row.round_wins}W · {row.total_score}</span>
+                            </div>
+                        ))}
+                    </div>
+                )}
                 <div className="flex gap-3 justify-center">
                     <button
                         onClick={onPlayAgain}
@@ -953,6 +1094,8 @@ export default function SnakeBattlePage() {
     }, []);
 
     const wsRef = useRef<WebSocket | null>(null);
+    const tickRef = useRef<number | null>(null); // Tick of the last frame applied; null while waiting for a keyframe
+    const [standings, setStandings] = useState<Standing[]>([]);
 
     const handleCreateRoom = async (settings: RoomSettings) => {
         try {
@@ -1045,13 +1188,32 @@ export default function SnakeBattlePage() {
                         setPlayerId(msg.payload.player_id);
                         break;
                     case 'GameState':
+                        tickRef.current = msg.payload.tick;
                         setGameState(msg.payload);
                         break;
+                    case 'Delta': {
+                        const delta = msg.payload;
+                        if (tickRef.current === null) break; // Resync already requested
+                        if (delta.tick !== tickRef.current + 1) {
+                            // Missed a frame: drop deltas until a fresh keyframe arrives
+                            tickRef.current = null;
+                            ws.send(JSON.stringify({ type: 'Resync' }));
+                            break;
+                        }
+                        tickRef.current = delta.tick;
+                        setGameState(prev => (prev && applyDelta(prev, delta)) ?? prev);
+                        break;
+                    }
                     case 'Error':
                         setError(msg.payload.message);
                         break;
-                    case 'GameOver':
-                        // Handled via GameState
+                    case 'AuthFailed':
+                        setError(msg.payload.message);
+                        break;
+                    case 'RoundOver':
+                    case 'MatchOver':
+                        // The phase change arrives as a keyframe; keep the scoreboard for the overlay
+                        setStandings(msg.payload.standings);
                         break;
                 }
             } catch (e) {
@@ -1273,8 +1435,21 @@ export default function SnakeBattlePage() {
                             {gameState.phase === 'Countdown' && gameState.countdown > 0 && (
                                 <CountdownOverlay countdown={gameState.countdown} />
                             )}
-                            {gameState.phase === 'GameOver' && (
-                                <GameOverOverlay winner={gameState.winner} onRestart={handleRestart} onPlayAgain={handlePlayAgain} />
+                            {gameState.phase === 'RoundOver' && (
+                                <RoundOverOverlay
+                                    round={gameState.round}
+                                    totalRounds={gameState.total_rounds}
+                                    winner={gameState.winner}
+                                    countdown={gameState.countdown}
+                                />
+                            )}
+                            {gameState.phase === 'MatchOver' && (
+                                <GameOverOverlay
+                                    winner={gameState.winner}
+                                    standings={standings}
+                                    onRestart={handleRestart}
+                                    onPlayAgain={handlePlayAgain}
+                                />
                             )}
                         </>
                     )}
@@ -0,0 +1,382 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{http::StatusCode, web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::future::{ready, Ready};
+use std::str::FromStr;
+
+/// Supabase issues user tokens for this audience
+const DEFAULT_AUDIENCE: &str = "authenticated";
+
+/// Identity proven by a verified Supabase JWT
+#[derive(Clone, Debug)]
+pub struct AuthUser {
+    pub user_id: String, // `sub` claim
+}
+
+/// An `AuthUser` listed in ADMIN_USER_IDS
+#[derive(Clone, Debug)]
+pub struct AdminUser(pub AuthUser);
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthError {
+    NotConfigured,
+    MissingToken,
+    Malformed,
+    UnknownKey,
+    InvalidSignature,
+    Expired,
+    InvalidAudience,
+    SubjectMismatch, // Client-supplied user_id differs from the token's `sub`
+    NotAdmin,
+}
+
+impl AuthError {
+    /// Stable machine-readable code sent to clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthError::NotConfigured => "auth_not_configured",
+            AuthError::MissingToken => "missing_token",
+            AuthError::Malformed => "malformed_token",
+            AuthError::UnknownKey => "unknown_key",
+            AuthError::InvalidSignature => "invalid_signature",
+            AuthError::Expired => "token_expired",
+            AuthError::InvalidAudience => "invalid_audience",
+            AuthError::SubjectMismatch => "subject_mismatch",
+            AuthError::NotAdmin => "not_admin",
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            AuthError::NotConfigured => "Token verification is not configured on this server",
+            AuthError::MissingToken => "An access token is required",
+            AuthError::Malformed => "Access token is malformed",
+            AuthError::UnknownKey => "Access token was signed with an unknown key",
+            AuthError::InvalidSignature => "Access token signature is invalid",
+            AuthError::Expired => "Access token has expired",
+            AuthError::InvalidAudience => "Access token was issued for another audience",
+            AuthError::SubjectMismatch => "user_id does not match the access token",
+            AuthError::NotAdmin => "Only server admins can do this",
+        };
+        f.write_str(message)
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::NotConfigured => StatusCode::SERVICE_UNAVAILABLE,
+            AuthError::NotAdmin => StatusCode::FORBIDDEN,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(serde_json::json!({
+            "error": self.to_string(),
+            "code": self.code(),
+        }))
+    }
+}
+
+enum KeySource {
+    Secret(DecodingKey), // HS256 project secret
+    Jwks(JwkSet),        // Asymmetric keys, picked by `kid`
+}
+
+/// Verifies Supabase access tokens (signature, expiry and audience)
+pub struct Authenticator {
+    keys: Option<KeySource>,
+    audience: String,
+    admins: HashSet<String>, // User ids allowed on operator endpoints
+}
+
+impl Authenticator {
+    /// Every token is rejected with `NotConfigured`
+    pub fn disabled() -> Self {
+        Authenticator { keys: None, audience: DEFAULT_AUDIENCE.to_string(), admins: HashSet::new() }
+    }
+
+    pub fn with_secret(secret: &[u8], audience: &str) -> Self {
+        Authenticator {
+            keys: Some(KeySource::Secret(DecodingKey::from_secret(secret))),
+            audience: audience.to_string(),
+            admins: HashSet::new(),
+        }
+    }
+
+    pub fn with_jwks(jwks: JwkSet, audience: &str) -> Self {
+        Authenticator {
+            keys: Some(KeySource::Jwks(jwks)),
+            audience: audience.to_string(),
+            admins: HashSet::new(),
+        }
+    }
+
+    pub fn with_admins<I: IntoIterator<Item = String>>(mut self, user_ids: I) -> Self {
+        self.admins = user_ids.into_iter().collect();
+        self
+    }
+
+    /// Reads SUPABASE_JWT_SECRET, or SUPABASE_JWKS_FILE, plus SUPABASE_JWT_AUDIENCE
+    /// and the comma-separated ADMIN_USER_IDS
+    pub fn from_env() -> Self {
+        let admins: Vec<String> = std::env::var("ADMIN_USER_IDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self::from_env_keys().with_admins(admins)
+    }
+
+    fn from_env_keys() -> Self {
+        let audience = std::env::var("SUPABASE_JWT_AUDIENCE")
+            .unwrap_or_else(|_| DEFAULT_AUDIENCE.to_string());
+
+        if let Ok(secret) = std::env::var("SUPABASE_JWT_SECRET") {
+            if !secret.is_empty() {
+                return Self::with_secret(secret.as_bytes(), &audience);
+            }
+        }
+
+        if let Ok(path) = std::env::var("SUPABASE_JWKS_FILE") {
+            let jwks = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str::<JwkSet>(&json).map_err(|e| e.to_string()));
+            match jwks {
+                Ok(jwks) => return Self::with_jwks(jwks, &audience),
+                Err(e) => log::error!("Failed to load JWKS from {}: {}", path, e),
+            }
+        }
+
+        Self::disabled()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    pub fn is_admin(&self, user: &AuthUser) -> bool {
+        self.admins.contains(&user.user_id)
+    }
+
+    pub fn verify(&self, token: &str) -> Result<AuthUser, AuthError> {
+        let keys = self.keys.as_ref().ok_or(AuthError::NotConfigured)?;
+        let header = decode_header(token).map_err(|_| AuthError::Malformed)?;
+
+        let (key, algorithm) = match keys {
+            KeySource::Secret(key) => (key.clone(), Algorithm::HS256),
+            KeySource::Jwks(jwks) => {
+                let jwk = match &header.kid {
+                    Some(kid) => jwks.find(kid),
+                    None => jwks.keys.first(),
+                }
+                .ok_or(AuthError::UnknownKey)?;
+                let key = DecodingKey::from_jwk(jwk).map_err(|_| AuthError::UnknownKey)?;
+                (key, key_algorithm(jwk).ok_or(AuthError::UnknownKey)?)
+            }
+        };
+
+        // The key decides the algorithm; a token can't pick a weaker one
+        if header.alg != algorithm {
+            return Err(AuthError::InvalidSignature);
+        }
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "sub", "aud"]);
+
+        let data = decode::<Claims>(token, &key, &validation).map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => AuthError::Expired,
+            ErrorKind::InvalidAudience => AuthError::InvalidAudience,
+            ErrorKind::InvalidSignature | ErrorKind::InvalidAlgorithm => AuthError::InvalidSignature,
+            _ => AuthError::Malformed,
+        })?;
+
+        Ok(AuthUser { user_id: data.claims.sub })
+    }
+
+    /// `verify`, and the token must belong to `claimed_user_id` when one is given
+    pub fn verify_as(&self, token: &str, claimed_user_id: Option<&str>) -> Result<AuthUser, AuthError> {
+        let user = self.verify(token)?;
+        if claimed_user_id.is_some_and(|claimed| claimed != user.user_id) {
+            return Err(AuthError::SubjectMismatch);
+        }
+        Ok(user)
+    }
+}
+
+/// The JWK's own `alg`, or the usual one for its key type
+fn key_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    if let Some(algorithm) = jwk.common.key_algorithm {
+        return Algorithm::from_str(&algorithm.to_string()).ok();
+    }
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => Some(Algorithm::RS256),
+        AlgorithmParameters::EllipticCurve(params) => match params.curve {
+            EllipticCurve::P256 => Some(Algorithm::ES256),
+            EllipticCurve::P384 => Some(Algorithm::ES384),
+            _ => None,
+        },
+        AlgorithmParameters::OctetKeyPair(_) => Some(Algorithm::EdDSA),
+        AlgorithmParameters::OctetKey(_) => Some(Algorithm::HS256),
+    }
+}
+
+// =============================================================================
+// ACTIX INTEGRATION
+// =============================================================================
+
+/// Middleware: a valid `Authorization: Bearer` token attaches an `AuthUser` to the
+/// request, an invalid one is rejected with 401. Requests without a token pass through.
+pub async fn identify(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let bearer = req.headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+
+    if let Some(token) = bearer {
+        let auth = req.app_data::<web::Data<Authenticator>>()
+            .ok_or(AuthError::NotConfigured)?;
+        let user = auth.verify(&token)?;
+        req.extensions_mut().insert(user);
+    }
+
+    next.call(req).await
+}
+
+/// Extractor for handlers behind `identify`; use `Option<AuthUser>` for optional auth
+impl FromRequest for AuthUser {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<AuthUser>().cloned().ok_or(AuthError::MissingToken))
+    }
+}
+
+impl FromRequest for AdminUser {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let user = req.extensions().get::<AuthUser>().cloned();
+        let admin = match (user, req.app_data::<web::Data<Authenticator>>()) {
+            (None, _) => Err(AuthError::MissingToken),
+            (Some(user), Some(auth)) if auth.is_admin(&user) => Ok(AdminUser(user)),
+            (Some(_), _) => Err(AuthError::NotAdmin),
+        };
+        ready(admin)
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    const SECRET: &[u8] = b"sekrit";
+
+    fn token(algorithm: Algorithm, kid: Option<&str>, secret: &[u8], sub: &str, aud: &str, expires_in: i64) -> String {
+        let header = Header { kid: kid.map(str::to_string), ..Header::new(algorithm) };
+        let claims = serde_json::json!({
+            "sub": sub,
+            "aud": aud,
+            "exp": chrono::Utc::now().timestamp() + expires_in,
+        });
+        encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn jwks(keys: serde_json::Value) -> JwkSet {
+        serde_json::from_value(serde_json::json!({ "keys": keys })).unwrap()
+    }
+
+    #[test]
+    fn secret_tokens_are_checked_for_expiry_signature_audience_and_subject() {
+        let auth = Authenticator::with_secret(SECRET, DEFAULT_AUDIENCE);
+        let good = token(Algorithm::HS256, None, SECRET, "u1", DEFAULT_AUDIENCE, 600);
+        assert_eq!(auth.verify(&good).unwrap().user_id, "u1");
+        assert_eq!(auth.verify_as(&good, Some("u1")).unwrap().user_id, "u1");
+        assert_eq!(auth.verify_as(&good, Some("u2")).unwrap_err(), AuthError::SubjectMismatch);
+
+        let expired = token(Algorithm::HS256, None, SECRET, "u1", DEFAULT_AUDIENCE, -600);
+        assert_eq!(auth.verify(&expired).unwrap_err(), AuthError::Expired);
+        let forged = token(Algorithm::HS256, None, b"guess", "u1", DEFAULT_AUDIENCE, 600);
+        assert_eq!(auth.verify(&forged).unwrap_err(), AuthError::InvalidSignature);
+        let elsewhere = token(Algorithm::HS256, None, SECRET, "u1", "anon", 600);
+        assert_eq!(auth.verify(&elsewhere).unwrap_err(), AuthError::InvalidAudience);
+        let other_alg = token(Algorithm::HS512, None, SECRET, "u1", DEFAULT_AUDIENCE, 600);
+        assert_eq!(auth.verify(&other_alg).unwrap_err(), AuthError::InvalidSignature);
+
+        // Swap in another payload under the original signature
+        let mut parts: Vec<&str> = good.split('.').collect();
+        let other = token(Algorithm::HS256, None, SECRET, "admin", DEFAULT_AUDIENCE, 600);
+        parts[1] = other.split('.').nth(1).unwrap();
+        assert_eq!(auth.verify(&parts.join(".")).unwrap_err(), AuthError::InvalidSignature);
+
+        assert_eq!(Authenticator::disabled().verify(&good).unwrap_err(), AuthError::NotConfigured);
+        assert_eq!(auth.verify("not a token").unwrap_err(), AuthError::Malformed);
+    }
+
+    #[test]
+    fn jwks_keys_decide_the_algorithm_not_the_token() {
+        let k = "c2Vrcml0"; // base64url("sekrit")
+        let auth = Authenticator::with_jwks(jwks(serde_json::json!([
+            { "kty": "oct", "kid": "hmac", "alg": "HS256", "k": k },
+            { "kty": "RSA", "kid": "rsa", "n": "sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri23bOdgWp4Dy1WlUzewbgBHod5pcM9H95GQRV3JDXboIRROSBigeC5yjU1hGzHHyXss8UDprecbAYxknTcQkhslANGRUZmdTOQ5qTRsLAt6BTYuyvVRdhS8exSZEy_c4gs_7svlJJQ4H9_NxsiIoLwAEk7-Q3UXERGYw_75IDrGA84-lA_-Ct4eTlXHBIY2EaV7t7LjJaynVJCpkv4LKjTTAumiGUIuQhrNhZLuF_RJLqHpM2kgWFLU7-VTdL1VbC2tejvcI2BlMkEpk1BzBZI0KQB0GaDWFLN-aEAw3vRw", "e": "AQAB" }
+        ])), DEFAULT_AUDIENCE);
+
+        let good = token(Algorithm::HS256, Some("hmac"), SECRET, "u1", DEFAULT_AUDIENCE, 600);
+        assert_eq!(auth.verify(&good).unwrap().user_id, "u1");
+        let other_alg = token(Algorithm::HS384, Some("hmac"), SECRET, "u1", DEFAULT_AUDIENCE, 600);
+        assert_eq!(auth.verify(&other_alg).unwrap_err(), AuthError::InvalidSignature);
+
+        // An RSA key without an `alg` only takes RS256, so an HMAC token can't borrow it
+        let confused = token(Algorithm::HS256, Some("rsa"), b"anything", "u1", DEFAULT_AUDIENCE, 600);
+        assert_eq!(auth.verify(&confused).unwrap_err(), AuthError::InvalidSignature);
+
+        let unknown = token(Algorithm::HS256, Some("gone"), SECRET, "u1", DEFAULT_AUDIENCE, 600);
+        assert_eq!(auth.verify(&unknown).unwrap_err(), AuthError::UnknownKey);
+    }
+
+    #[actix_web::test]
+    async fn only_listed_users_are_admins() {
+        let auth = web::Data::new(Authenticator::with_secret(SECRET, DEFAULT_AUDIENCE).with_admins(["ops".to_string()]));
+        let admin = |user: Option<&str>| {
+            let req = actix_web::test::TestRequest::default().app_data(auth.clone()).to_http_request();
+            if let Some(user) = user {
+                req.extensions_mut().insert(AuthUser { user_id: user.to_string() });
+            }
+            AdminUser::extract(&req).into_inner().map(|admin| admin.0.user_id)
+        };
+
+        assert_eq!(admin(Some("ops")).unwrap(), "ops");
+        assert_eq!(admin(Some("u1")).unwrap_err(), AuthError::NotAdmin);
+        assert_eq!(admin(None).unwrap_err(), AuthError::MissingToken);
+        assert_eq!(AuthError::NotAdmin.status_code(), StatusCode::FORBIDDEN);
+    }
+}
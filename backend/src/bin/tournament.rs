@@ -0,0 +1,210 @@
+//! Headless bot-vs-bot matches, for comparing strategies and catching rule regressions.
+//!
+//!     cargo run --release --bin tournament -- --strategies Hard,Pathfinder --matches 1000
+//!
+//! Run with `--help` for every option. The report goes to stdout (or `--out`) as JSON or CSV.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use chill_space_backend::games::snake::{
+    BotRegistry, FileMapStore, HardWeights, MapCatalog, Policy, RoomSettings, Tournament,
+};
+
+const USAGE: &str = "\
+Usage: tournament [options]
+
+  --strategies A,B,...    Strategies to rotate through the seats (default: Easy,Medium,Hard)
+  --players N             Seats per match (default: number of strategies, at least 2)
+  --matches N             Matches to play (default: 100)
+  --seed N                Seed of the first match; match i uses seed + i (default: 0)
+  --maps a,b,...          Built-in or uploaded map ids to cycle through; 'open' is the plain board (default: open)
+  --mode Classic|Arena    (default: Classic)
+  --size Small|Medium|Large
+  --speed Slow|Normal|Fast
+  --rounds N              Best-of-N (default: 1)
+  --head-on BothDie|LongerWins
+  --wrap                  Wrap the board edges
+  --power-ups             Spawn power-ups (off by default)
+  --max-ticks N           Matches still running after N ticks count as timeouts (default: 5000)
+  --threads N             Worker threads (default: all cores)
+  --expert-rollouts N     Fixed search for Expert instead of its time budget, so results repeat
+  --hard NAME=F,S,P       Also register a Hard bot called NAME with food, space and power-up weights
+  --policy PATH           Also register a trained policy (JSON) as 'policy:NAME'
+  --format json|csv       (default: json)
+  --out PATH              Write the report here instead of stdout
+";
+
+struct Options {
+    tournament: Tournament,
+    threads: usize,
+    csv: bool,
+    out: Option<String>,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
+
+    let options = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let tournament = &options.tournament;
+    log::info!(
+        "Playing {} matches of {} on {} threads",
+        tournament.matches, tournament.strategies.join(" / "), options.threads
+    );
+    let started = Instant::now();
+    let report = tournament.run(options.threads);
+    log::info!(
+        "Done in {:.1}s: {} timeouts, {} draws",
+        started.elapsed().as_secs_f64(), report.timeouts, report.draws
+    );
+
+    let output = if options.csv {
+        report.to_csv()
+    } else {
+        serde_json::to_string_pretty(&report).expect("report serializes") + "\n"
+    };
+    match &options.out {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, output) {
+                eprintln!("Failed to write {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+        None => print!("{}", output),
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut registry = BotRegistry::builtin();
+    let mut strategies = vec!["Easy".to_string(), "Medium".to_string(), "Hard".to_string()];
+    let mut players = None;
+    let mut map_ids = vec!["open".to_string()];
+    let mut settings = RoomSettings { power_ups_enabled: false, ..RoomSettings::default() };
+    let mut matches = 100;
+    let mut seed = 0;
+    let mut max_ticks = 5_000;
+    let mut threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut csv = false;
+    let mut out = None;
+
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--help" | "-h" => {
+                print!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "--strategies" => strategies = list(&value()?),
+            "--players" => players = Some(number(&flag, &value()?)?),
+            "--matches" => matches = number(&flag, &value()?)?,
+            "--seed" => seed = number(&flag, &value()?)?,
+            "--maps" => map_ids = list(&value()?),
+            "--mode" => settings.mode = variant(&flag, &value()?)?,
+            "--size" => settings.map_size = variant(&flag, &value()?)?,
+            "--speed" => settings.speed = variant(&flag, &value()?)?,
+            "--rounds" => settings.rounds = number(&flag, &value()?)?,
+            "--head-on" => settings.head_on = variant(&flag, &value()?)?,
+            "--wrap" => settings.wrap_edges = true,
+            "--power-ups" => settings.power_ups_enabled = true,
+            "--max-ticks" => max_ticks = number(&flag, &value()?)?,
+            "--threads" => threads = number(&flag, &value()?)?,
+            "--expert-rollouts" => registry.fix_expert_rollouts(number(&flag, &value()?)?),
+            "--hard" => {
+                let (name, weights) = hard_variant(&value()?)?;
+                registry.register_hard(&name, weights);
+            }
+            "--policy" => {
+                let path = value()?;
+                let policy = std::fs::read(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|bytes| serde_json::from_slice::<Policy>(&bytes).map_err(|e| e.to_string()))
+                    .and_then(|policy| policy.validate().map(|()| policy))
+                    .map_err(|e| format!("Failed to load policy {}: {}", path, e))?;
+                registry.register_policy(Arc::new(policy));
+            }
+            "--format" => {
+                csv = match value()?.as_str() {
+                    "json" => false,
+                    "csv" => true,
+                    other => return Err(format!("Unknown format '{}'", other)),
+                }
+            }
+            "--out" => out = Some(value()?),
+            other => return Err(format!("Unknown option '{}'", other)),
+        }
+    }
+
+    // Flags can come in any order, so names are only checked once every variant is registered
+    let names: Vec<String> = registry.list().into_iter().map(|info| info.name).collect();
+    let strategies = strategies.iter()
+        .map(|name| {
+            registry.resolve(name)
+                .map(str::to_string)
+                .ok_or_else(|| format!("Unknown strategy '{}' (have: {})", name, names.join(", ")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if strategies.is_empty() {
+        return Err("--strategies needs at least one strategy".to_string());
+    }
+
+    let catalog = MapCatalog::with_store(Arc::new(FileMapStore::from_env()));
+    let mut maps = Vec::new();
+    for id in &map_ids {
+        if id == "open" {
+            maps.push(None);
+            continue;
+        }
+        match catalog.get(id) {
+            Ok(Some(map)) => maps.push(Some(map)),
+            Ok(None) => return Err(format!("Unknown map '{}'", id)),
+            Err(e) => return Err(format!("Failed to load map '{}': {}", id, e)),
+        }
+    }
+
+    let mut tournament = Tournament::new(Arc::new(registry), strategies);
+    let max_seats = settings.mode.max_players();
+    tournament.players = players.unwrap_or(tournament.players).clamp(1, max_seats);
+    tournament.matches = matches;
+    tournament.seed = seed;
+    tournament.maps = maps;
+    tournament.max_ticks = max_ticks;
+    tournament.settings = settings;
+    Ok(Options { tournament, threads, csv, out })
+}
+
+fn list(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a number, got '{}'", flag, value))
+}
+
+/// A settings enum by its variant name, e.g. `Arena` or `LongerWins`
+fn variant<T: serde::de::DeserializeOwned>(flag: &str, value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("{} doesn't accept '{}'", flag, value))
+}
+
+/// `NAME=food,space,power_up`
+fn hard_variant(value: &str) -> Result<(String, HardWeights), String> {
+    let bad = || format!("--hard expects NAME=FOOD,SPACE,POWER_UP, got '{}'", value);
+    let (name, weights) = value.split_once('=').ok_or_else(bad)?;
+    let weights: Vec<f32> = weights.split(',')
+        .map(|w| w.trim().parse().map_err(|_| bad()))
+        .collect::<Result<_, _>>()?;
+    match (name.trim(), weights.as_slice()) {
+        ("", _) => Err(bad()),
+        (name, &[food, space, power_up]) => Ok((name.to_string(), HardWeights { food, space, power_up })),
+        _ => Err(bad()),
+    }
+}
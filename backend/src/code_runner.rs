@@ -1,5 +1,10 @@
-use std::process::{Command, Stdio};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use std::fs;
 use tokio::time::timeout;
 
@@ -7,6 +12,25 @@ use tokio::time::timeout;
 const MAX_EXECUTION_TIME_SECS: u64 = 10;
 const MAX_CODE_LENGTH: usize = 50_000;
 const MAX_OUTPUT_LENGTH: usize = 100_000; // 100KB
+const MAX_BOT_LINE: usize = 4_096;       // Longest single reply a bot process may print
+const BOT_QUEUE: usize = 4;              // Lines waiting for a bot that has fallen behind
+const BOT_CPU_SECS: u64 = 600;           // CPU time a bot process may use in all
+const BOT_MEMORY_BYTES: u64 = 256 << 20; // Heap (RLIMIT_DATA); address-space limits break the JVM and V8
+const BOT_MAX_TASKS: u64 = 64;           // Processes and threads a bot may add
+const BOT_MAX_FILES: u64 = 64;           // Open file descriptors
+const BOT_LIFETIME: Duration = Duration::from_secs(30 * 60); // Then it's killed, game or no game
+const JAVA_COMPILE_TIMEOUT: Duration = Duration::from_secs(30);
+const JAVA_CLASS_BYTES: u64 = 16 << 20;  // Largest file javac may write
+const DEFAULT_MAX_BOT_PROCESSES: usize = 16;
+const DEFAULT_MAX_BOT_PROCESSES_PER_USER: usize = 4;
+/// Keeps the JVM inside the memory and thread limits
+const JAVA_BOT_FLAGS: &[&str] = &[
+    "-Xmx128m", "-Xss512k", "-XX:+UseSerialGC", "-XX:-UsePerfData",
+    "-XX:TieredStopAtLevel=1", "-XX:CICompilerCount=1",
+];
+
+/// Languages both `execute` and `BotProcess` can run
+pub const LANGUAGES: &[&str] = &["python", "javascript", "java"];
 
 /// Dangerous patterns to block (case-insensitive check)
 const PYTHON_BLOCKED: &[&str] = &[
@@ -37,7 +61,7 @@ const JAVA_BLOCKED: &[&str] = &[
 ];
 
 /// Check if code contains dangerous patterns
-fn check_security(language: &str, code: &str) -> Result<(), String> {
+pub fn check_security(language: &str, code: &str) -> Result<(), String> {
     // Check code length
     if code.len() > MAX_CODE_LENGTH {
         return Err(format!(
@@ -224,3 +248,347 @@ fn extract_java_class_name(code: &str) -> Option<String> {
     }
     None
 }
+
+// =============================================================================
+// LONG-RUNNING BOT PROCESSES
+// =============================================================================
+
+/// How many bot processes may run at once, overall and per user
+pub struct BotProcessLimits {
+    total: usize,
+    per_user: usize,
+    running: Mutex<HashMap<String, usize>>, // Owner -> live processes
+}
+
+impl BotProcessLimits {
+    pub fn new(total: usize, per_user: usize) -> Self {
+        BotProcessLimits { total, per_user, running: Mutex::new(HashMap::new()) }
+    }
+
+    /// `MAX_CODE_BOTS` overall and `MAX_CODE_BOTS_PER_USER`
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: usize| {
+            std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+        };
+        Self::new(
+            read("MAX_CODE_BOTS", DEFAULT_MAX_BOT_PROCESSES),
+            read("MAX_CODE_BOTS_PER_USER", DEFAULT_MAX_BOT_PROCESSES_PER_USER),
+        )
+    }
+
+    /// How many more processes `owner` could start right now
+    pub fn available(&self, owner: &str) -> usize {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        let total: usize = running.values().sum();
+        let own = running.get(owner).copied().unwrap_or(0);
+        self.total.saturating_sub(total).min(self.per_user.saturating_sub(own))
+    }
+
+    /// Take a slot for one process, given back when the permit drops
+    pub fn acquire(self: &Arc<Self>, owner: &str) -> Result<BotPermit, String> {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        let total: usize = running.values().sum();
+        let own = running.entry(owner.to_string()).or_insert(0);
+        if total >= self.total {
+            return Err("Too many bot programs are running; try again later".to_string());
+        }
+        if *own >= self.per_user {
+            return Err(format!("You already have {} bot programs running", self.per_user));
+        }
+        *own += 1;
+        Ok(BotPermit { limits: self.clone(), owner: owner.to_string() })
+    }
+}
+
+/// One running process's slot in `BotProcessLimits`
+pub struct BotPermit {
+    limits: Arc<BotProcessLimits>,
+    owner: String,
+}
+
+impl Drop for BotPermit {
+    fn drop(&mut self) {
+        let mut running = self.limits.running.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = running.get_mut(&self.owner) {
+            *count -= 1;
+            if *count == 0 {
+                running.remove(&self.owner);
+            }
+        }
+    }
+}
+
+/// A program that stays up for a whole game: it reads one line at a time on stdin
+/// and answers each with one line on stdout. Runs with the same checks as `execute`,
+/// an empty environment, no stderr and (on Unix) resource limits in a process group
+/// of its own; killed with everything it started when dropped or too old.
+pub struct BotProcess {
+    child: Child,
+    started: Instant,
+    lines: mpsc::SyncSender<String>,  // To the stdin writer thread
+    replies: mpsc::Receiver<String>,  // From the stdout reader thread
+    unanswered: usize,                // Lines sent that haven't been answered yet
+    workdir: Option<PathBuf>,         // Compiled Java classes, removed on drop
+    _permit: BotPermit,
+}
+
+impl BotProcess {
+    /// Check and start `code` in the slot `permit` holds. Blocks while Java compiles.
+    pub fn spawn(language: &str, code: &str, permit: BotPermit) -> Result<Self, String> {
+        check_security(language, code)?;
+
+        let (mut command, workdir) = match language {
+            "python" => {
+                let mut command = Command::new(if cfg!(unix) { "python3" } else { "python" });
+                command.arg("-u").arg("-c").arg(code);
+                (command, None)
+            }
+            "javascript" => {
+                let mut command = Command::new("node");
+                command.arg("-e").arg(code);
+                (command, None)
+            }
+            "java" => {
+                let (dir, class_name) = compile_java_bot(code)?;
+                let mut command = Command::new("java");
+                command.args(JAVA_BOT_FLAGS).arg("-cp").arg(&dir).arg(class_name);
+                (command, Some(dir))
+            }
+            _ => return Err(format!("Language '{}' is not supported yet", language)),
+        };
+
+        #[cfg(unix)]
+        limit_resources(&mut command, 0);
+        let spawned = command
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .current_dir(std::env::temp_dir())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                if let Some(dir) = &workdir {
+                    let _ = fs::remove_dir_all(dir);
+                }
+                return Err(format!("Failed to start {} bot: {}", language, e));
+            }
+        };
+        let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            return Err("Bot process has no stdin/stdout".to_string());
+        };
+
+        // Pipes are drained on their own threads so a bot that stops reading or
+        // writing can never block the caller
+        let (lines, queued) = mpsc::sync_channel::<String>(BOT_QUEUE);
+        thread::spawn(move || {
+            for line in queued {
+                let written = stdin.write_all(line.as_bytes())
+                    .and_then(|_| stdin.write_all(b"\n"))
+                    .and_then(|_| stdin.flush());
+                if written.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (answers, replies) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            loop {
+                let mut line = String::new();
+                match (&mut reader).take(MAX_BOT_LINE as u64).read_line(&mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) if !line.ends_with('\n') => break, // Runaway output: stop listening
+                    Ok(_) => {
+                        if answers.send(line.trim().to_string()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(BotProcess { child, started: Instant::now(), lines, replies, unanswered: 0, workdir, _permit: permit })
+    }
+
+    /// Queue a line for the bot; false if it has exited or is too far behind to take more
+    pub fn send(&mut self, line: String) -> bool {
+        let sent = self.lines.try_send(line).is_ok();
+        if sent {
+            self.unanswered += 1;
+        }
+        sent
+    }
+
+    /// The answer to the last line sent, if it comes before `deadline`.
+    /// Late answers to earlier lines are skipped rather than applied out of turn.
+    pub fn reply(&mut self, deadline: Instant) -> Option<String> {
+        while self.unanswered > 0 {
+            let reply = self.replies.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok()?;
+            self.unanswered -= 1;
+            if self.unanswered == 0 {
+                return Some(reply);
+            }
+        }
+        None
+    }
+
+    /// True once the process has exited, or been killed for outliving `BOT_LIFETIME`
+    pub fn has_exited(&mut self) -> bool {
+        if self.started.elapsed() > BOT_LIFETIME {
+            self.kill();
+        }
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+
+    fn kill(&mut self) {
+        kill_group(&mut self.child);
+    }
+}
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        self.kill();
+        let _ = self.child.wait();
+        if let Some(dir) = &self.workdir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Kill a process started under `limit_resources`. It leads its own process group,
+/// so this takes anything it started too.
+fn kill_group(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+/// Runs the bot in a new session (so it can be killed as a group) under rlimits, writing
+/// no file bigger than `max_file_bytes`. Only async-signal-safe calls happen after the
+/// fork; the process count is read first.
+#[cfg(unix)]
+fn limit_resources(command: &mut Command, max_file_bytes: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let tasks = BOT_MAX_TASKS + user_task_count();
+    let limits = [
+        (libc::RLIMIT_CPU, BOT_CPU_SECS),
+        (libc::RLIMIT_DATA, BOT_MEMORY_BYTES),
+        (libc::RLIMIT_NPROC, tasks),
+        (libc::RLIMIT_NOFILE, BOT_MAX_FILES),
+        (libc::RLIMIT_FSIZE, max_file_bytes),
+        (libc::RLIMIT_CORE, 0),
+    ];
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            for (resource, value) in limits {
+                let limit = libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t };
+                if libc::setrlimit(resource, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            libc::nice(10);
+            Ok(())
+        });
+    }
+}
+
+/// Processes and threads already running as this user, which RLIMIT_NPROC counts too
+#[cfg(unix)]
+fn user_task_count() -> u64 {
+    let uid = unsafe { libc::getuid() }.to_string();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().bytes().all(|b| b.is_ascii_digit()))
+        .filter_map(|entry| fs::read_to_string(entry.path().join("status")).ok())
+        .filter(|status| {
+            status.lines().any(|line| line.starts_with("Uid:") && line.split_whitespace().nth(1) == Some(uid.as_str()))
+        })
+        .map(|status| {
+            status.lines()
+                .find_map(|line| line.strip_prefix("Threads:"))
+                .and_then(|threads| threads.trim().parse().ok())
+                .unwrap_or(1)
+        })
+        .sum()
+}
+
+/// Compile into a directory of its own, since several bots may share a class name
+fn compile_java_bot(code: &str) -> Result<(PathBuf, String), String> {
+    let dir = std::env::temp_dir().join(format!("chill_space_bot_{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create temp dir: {}", e))?;
+
+    let class_name = extract_java_class_name(code).unwrap_or_else(|| "Main".to_string());
+    let file_path = dir.join(format!("{}.java", class_name));
+    let compiled = fs::write(&file_path, code)
+        .map_err(|e| format!("Failed to write Java file: {}", e))
+        .and_then(|_| run_javac(&file_path, &dir));
+
+    match compiled {
+        Ok(()) => Ok((dir, class_name)),
+        Err(e) => {
+            let _ = fs::remove_dir_all(&dir);
+            Err(e)
+        }
+    }
+}
+
+/// javac under the same limits as the bots it builds, killed if it takes longer
+/// than `JAVA_COMPILE_TIMEOUT`
+fn run_javac(file_path: &Path, dir: &Path) -> Result<(), String> {
+    let mut command = Command::new("javac");
+    command.args(JAVA_BOT_FLAGS.iter().map(|flag| format!("-J{}", flag))).arg(file_path);
+    #[cfg(unix)]
+    limit_resources(&mut command, JAVA_CLASS_BYTES);
+    let mut child = command
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to compile Java: {}. Make sure JDK is installed.", e))?;
+
+    // Drained on its own thread so a flood of errors can't stall javac
+    let mut stderr = child.stderr.take();
+    let errors = thread::spawn(move || {
+        let mut errors = Vec::new();
+        if let Some(stderr) = &mut stderr {
+            let _ = stderr.take(MAX_OUTPUT_LENGTH as u64).read_to_end(&mut errors);
+        }
+        String::from_utf8_lossy(&errors).to_string()
+    });
+
+    let deadline = Instant::now() + JAVA_COMPILE_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            Ok(None) => break Err(format!("Compiling took longer than {} seconds", JAVA_COMPILE_TIMEOUT.as_secs())),
+            Err(e) => break Err(format!("Failed to compile Java: {}", e)),
+        }
+    };
+    if status.is_err() {
+        kill_group(&mut child);
+        let _ = child.wait();
+    }
+    let errors = errors.join().unwrap_or_default();
+    match status? {
+        status if status.success() => Ok(()),
+        _ => Err(truncate_output(errors)),
+    }
+}
@@ -0,0 +1,1020 @@
+use rand::{Rng, RngCore};
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::code_bots::{BotSource, CodeBot};
+use crate::code_runner::BotPermit;
+use super::gym::{Policy, PolicyBot};
+use super::{Direction, Engine, GamePhase, GameState, Input, Player, Point};
+
+/// Steers bots added without a strategy, and humans who dropped out
+pub const DEFAULT_BOT_STRATEGY: &str = "Medium";
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+const SPACE_CAP: usize = 400;       // Flood fills stop counting here; plenty of room to turn around in
+const HARD_SPACE_CAP: usize = 10;   // Hard's short-sighted flood fill
+const INTERCEPT_LOOKAHEAD: i32 = 6; // Cells ahead of a victim's head the cut-off bot aims for
+
+const ADAPTIVE: &str = "Adaptive";
+pub const ADAPTIVE_START_SKILL: f32 = 0.5;
+const ADAPTIVE_MAX_LOOKAHEAD: usize = 40; // Open cells a full-skill bot wants behind each move
+const RATING_STEP: f32 = 0.15;            // Most a single match moves the baseline
+const IN_MATCH_SWING: f32 = 0.25;         // Most the current match moves skill off the baseline
+const SCORE_GAP_SCALE: f32 = 50.0;        // Score lead that counts as a rout (five food)
+const DEATH_GAP_SCALE: f32 = 3.0;         // Extra deaths that count as a rout
+const SURVIVAL_GAP_MIN_TICKS: u64 = 20;   // Round time before survival says anything
+const SKILL_STEPS: f32 = 20.0;            // Skill is rounded to twentieths, so it doesn't flicker
+
+const EXPERT: &str = "Expert";
+const EXPERT_BUDGET: Duration = Duration::from_millis(10);      // Search time per Expert per tick
+const EXPERT_ROOM_BUDGET: Duration = Duration::from_millis(40); // Shared by all Experts in a room
+const EXPERT_MAX_ROLLOUTS: usize = 600;
+const EXPERT_DEPTH: usize = 12;        // Ticks played out per rollout
+const ROLLOUT_NOISE: f64 = 0.2;        // Chance a simulated snake takes any safe turn instead of the greedy one
+
+// =============================================================================
+// STRATEGY TRAIT & REGISTRY
+// =============================================================================
+
+/// Picks a direction for one bot each tick. Every bot gets its own instance,
+/// so a strategy may remember things between ticks.
+pub trait BotStrategy: Send {
+    /// Called for every bot before any of them decides, so slow thinkers can start at once
+    fn prepare(&mut self, _state: &GameState, _player_id: &str) {}
+
+    fn decide(&mut self, state: &GameState, player_id: &str, rng: &mut dyn RngCore) -> Option<Direction>;
+}
+
+type Factory = Arc<dyn Fn() -> Box<dyn BotStrategy> + Send + Sync>;
+
+/// Name and blurb for strategy pickers
+#[derive(Clone, Debug, Serialize)]
+pub struct StrategyInfo {
+    pub name: String,
+    pub description: String,
+}
+
+/// Strategies bots can be created from, by name
+#[derive(Clone, Default)]
+pub struct BotRegistry {
+    strategies: BTreeMap<String, (String, Factory)>, // name -> (description, factory)
+}
+
+impl BotRegistry {
+    /// The strategies that ship with the backend
+    pub fn builtin() -> Self {
+        let mut registry = BotRegistry::default();
+        registry.register("Easy", "Wanders at random, sometimes heads for food", || Box::new(Easy));
+        registry.register("Medium", "Heads for the nearest food and avoids walls", || Box::new(Medium));
+        registry.register("Hard", "Weighs food, nearby space and power-ups for each move", || Box::new(Hard::default()));
+        registry.register("Pathfinder", "Follows the shortest path to the nearest reachable food", || Box::new(Pathfinder));
+        registry.register("TailChaser", "Only eats when it can still reach its own tail, and follows it otherwise", || Box::new(TailChaser));
+        registry.register("Interceptor", "Cuts across the path of the nearest opponent", || Box::new(Interceptor));
+        registry.register(ADAPTIVE, "Plays harder or softer to keep games against the humans close", || Box::new(Adaptive));
+        registry.register(EXPERT, "Plays each move a dozen ticks ahead against likely opponent moves", || Box::new(Expert::default()));
+        registry
+    }
+
+    /// Add or replace a strategy
+    pub fn register(
+        &mut self,
+        name: &str,
+        description: &str,
+        factory: impl Fn() -> Box<dyn BotStrategy> + Send + Sync + 'static,
+    ) {
+        self.strategies.insert(name.to_string(), (description.to_string(), Arc::new(factory)));
+    }
+
+    /// A Hard bot scoring moves with its own weights, for tuning them against the default
+    pub fn register_hard(&mut self, name: &str, weights: HardWeights) {
+        let description = format!(
+            "Hard with weights food {}, space {}, power-ups {}",
+            weights.food, weights.space, weights.power_up
+        );
+        self.register(name, &description, move || Box::new(Hard { weights }));
+    }
+
+    /// A submitted program, under `source.strategy()`. Each bot made from it runs in
+    /// one of `permits`; any beyond those never start.
+    pub fn register_code_bot(&mut self, source: &BotSource, permits: Vec<BotPermit>) {
+        let source = source.clone();
+        let permits = Mutex::new(permits);
+        let description = format!("{} bot by {}", source.language, source.author.as_deref().unwrap_or("a guest"));
+        self.register(&source.strategy(), &description, move || {
+            let permit = permits.lock().unwrap_or_else(|e| e.into_inner()).pop();
+            Box::new(CodeBot::start(&source, permit))
+        });
+    }
+
+    /// A trained policy, under `policy.strategy()`
+    pub fn register_policy(&mut self, policy: Arc<Policy>) {
+        let description = match policy.description.trim() {
+            "" => "Trained policy".to_string(),
+            description => description.to_string(),
+        };
+        self.register(&policy.strategy(), &description, move || Box::new(PolicyBot::new(policy.clone())));
+    }
+
+    /// Make Expert stop after a fixed number of rollouts instead of when its time is
+    /// up, so its games depend only on the seed and not on how busy the machine is
+    pub fn fix_expert_rollouts(&mut self, rollouts: usize) {
+        self.register(EXPERT, &format!("Expert limited to {} rollouts a move", rollouts), move || {
+            Box::new(Expert { budget: Duration::MAX, room_budget: Duration::MAX, max_rollouts: rollouts })
+        });
+    }
+
+    /// Registered spelling of `name`, matched case-insensitively
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        self.strategies.keys().find(|key| key.eq_ignore_ascii_case(name)).map(String::as_str)
+    }
+
+    pub fn create(&self, name: &str) -> Option<Box<dyn BotStrategy>> {
+        let (_, factory) = self.strategies.get(self.resolve(name)?)?;
+        Some(factory())
+    }
+
+    pub fn list(&self) -> Vec<StrategyInfo> {
+        self.strategies.iter()
+            .map(|(name, (description, _))| StrategyInfo { name: name.clone(), description: description.clone() })
+            .collect()
+    }
+}
+
+/// The bots of one game, each created from its player's strategy on first use
+pub struct BotBrains {
+    registry: Arc<BotRegistry>,
+    brains: HashMap<String, Box<dyn BotStrategy>>, // player id -> instance
+}
+
+impl BotBrains {
+    pub fn new(registry: Arc<BotRegistry>) -> Self {
+        BotBrains { registry, brains: HashMap::new() }
+    }
+
+    pub fn registry(&self) -> &BotRegistry {
+        &self.registry
+    }
+
+    /// Next direction for every living bot, and for humans who dropped out
+    pub fn decide_all(&mut self, state: &GameState, rng: &mut dyn RngCore) -> Vec<Input> {
+        self.brains.retain(|id, _| state.players.contains_key(id));
+
+        let steered: Vec<&Player> = state.players.values()
+            .filter(|player| (player.is_bot || !player.connected) && player.snake.alive)
+            .collect();
+        for player in &steered {
+            if let Some(brain) = self.brain(player) {
+                brain.prepare(state, &player.id);
+            }
+        }
+
+        let mut inputs = Vec::new();
+        for player in steered {
+            let Some(brain) = self.brains.get_mut(&player.id) else {
+                continue;
+            };
+            if let Some(direction) = brain.decide(state, &player.id, rng) {
+                inputs.push(Input { player_id: player.id.clone(), direction });
+            }
+        }
+        inputs
+    }
+
+    /// Create every bot's brain now rather than on its first move, so programs
+    /// that take a while to start can get going in the lobby
+    pub fn warm_up(&mut self, state: &GameState) {
+        for player in state.players.values().filter(|player| player.is_bot) {
+            self.brain(player);
+        }
+    }
+
+    fn brain(&mut self, player: &Player) -> Option<&mut Box<dyn BotStrategy>> {
+        match self.brains.entry(player.id.clone()) {
+            Entry::Occupied(entry) => Some(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let name = player.strategy.as_deref().unwrap_or(DEFAULT_BOT_STRATEGY);
+                let Some(brain) = self.registry.create(name) else {
+                    log::warn!("No bot strategy named '{}'", name);
+                    return None;
+                };
+                Some(entry.insert(brain))
+            }
+        }
+    }
+}
+
+// =============================================================================
+// BUILT-IN STRATEGIES
+// =============================================================================
+
+/// 70% random, 30% toward food
+struct Easy;
+
+impl BotStrategy for Easy {
+    fn decide(&mut self, state: &GameState, player_id: &str, rng: &mut dyn RngCore) -> Option<Direction> {
+        let snake = &state.players.get(player_id)?.snake;
+        let (head, current) = (snake.head(), snake.direction);
+
+        // 30% chance to seek food
+        if rng.gen_bool(0.3) {
+            if let Some(target) = state.nearest_food(head) {
+                return Some(state.direction_toward(head, &target));
+            }
+        }
+
+        // 70% random movement
+        let options = valid_turns(current);
+        Some(options[rng.gen_range(0..options.len())])
+    }
+}
+
+/// Straight for the nearest food, turning aside from walls
+struct Medium;
+
+impl BotStrategy for Medium {
+    fn decide(&mut self, state: &GameState, player_id: &str, _rng: &mut dyn RngCore) -> Option<Direction> {
+        let snake = &state.players.get(player_id)?.snake;
+        let (head, current) = (snake.head(), snake.direction);
+        let Some(target) = state.nearest_food(head) else {
+            return Some(current);
+        };
+
+        let desired = state.direction_toward(head, &target);
+        if state.is_direction_safe(head, desired) {
+            return Some(desired);
+        }
+
+        // Try alternative safe directions
+        valid_turns(current).into_iter()
+            .find(|&dir| state.is_direction_safe(head, dir))
+            .or(Some(current))
+    }
+}
+
+/// How much the Hard bot cares about each part of a move's score
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HardWeights {
+    pub food: f32,     // Per cell closer to the nearest food
+    pub space: f32,    // Per open cell reachable after the move (up to a small cap)
+    pub power_up: f32, // Per power-up within a few cells
+}
+
+impl Default for HardWeights {
+    fn default() -> Self {
+        HardWeights { food: 1.0, space: 0.1, power_up: 2.0 }
+    }
+}
+
+/// Scores each turn on food distance, nearby space and power-ups
+#[derive(Default)]
+struct Hard {
+    weights: HardWeights,
+}
+
+impl BotStrategy for Hard {
+    fn decide(&mut self, state: &GameState, player_id: &str, _rng: &mut dyn RngCore) -> Option<Direction> {
+        let snake = &state.players.get(player_id)?.snake;
+        let (head, current) = (snake.head(), snake.direction);
+        let Some(target) = state.nearest_food(head) else {
+            return Some(current);
+        };
+
+        let mut best_dir = current;
+        let mut best_score = -1000.0;
+        for dir in valid_turns(current) {
+            let score = state.evaluate_direction(head, dir, &target, &self.weights);
+            if score > best_score {
+                best_score = score;
+                best_dir = dir;
+            }
+        }
+        Some(best_dir)
+    }
+}
+
+/// Breadth-first search to the closest food it can actually reach
+struct Pathfinder;
+
+impl BotStrategy for Pathfinder {
+    fn decide(&mut self, state: &GameState, player_id: &str, _rng: &mut dyn RngCore) -> Option<Direction> {
+        let snake = &state.players.get(player_id)?.snake;
+        let (head, current, length) = (*snake.head(), snake.direction, snake.body.len());
+
+        let food: HashSet<Point> = state.food.iter().copied().collect();
+        if let Some(dir) = state.first_step(head, current, |p| food.contains(&p), |p| !state.is_blocked(p)) {
+            // Only if the first step leaves room to keep going afterwards
+            let next = state.next_position(&head, dir);
+            if !state.contested(player_id, next) && state.open_area(next, length + 1) > length {
+                return Some(dir);
+            }
+        }
+        Some(state.roomiest_move(player_id, head, current))
+    }
+}
+
+/// Survival first: eat only when the tail stays reachable afterwards, otherwise follow it
+struct TailChaser;
+
+impl BotStrategy for TailChaser {
+    fn decide(&mut self, state: &GameState, player_id: &str, _rng: &mut dyn RngCore) -> Option<Direction> {
+        let snake = &state.players.get(player_id)?.snake;
+        let (head, current) = (*snake.head(), snake.direction);
+        let tail = *snake.body.back()?;
+        // The tail moves on as we do, so its cell counts as open
+        let open = |p: Point| p == tail || !state.is_blocked(p);
+        let reaches_tail = |from: Point| from == tail || state.path_exists(from, |p| p == tail, open);
+
+        let food: HashSet<Point> = state.food.iter().copied().collect();
+        if let Some(dir) = state.first_step(head, current, |p| food.contains(&p), |p| !state.is_blocked(p)) {
+            let next = state.next_position(&head, dir);
+            if !state.contested(player_id, next) && reaches_tail(next) {
+                return Some(dir);
+            }
+        }
+        if let Some(dir) = state.first_step(head, current, |p| p == tail, open) {
+            if !state.contested(player_id, state.next_position(&head, dir)) {
+                return Some(dir);
+            }
+        }
+        Some(state.roomiest_move(player_id, head, current))
+    }
+}
+
+/// Races for the cells just ahead of the nearest opponent, food otherwise
+struct Interceptor;
+
+impl BotStrategy for Interceptor {
+    fn decide(&mut self, state: &GameState, player_id: &str, rng: &mut dyn RngCore) -> Option<Direction> {
+        let me = state.players.get(player_id)?;
+        let (head, current, length) = (*me.snake.head(), me.snake.direction, me.snake.body.len());
+
+        let ahead = state.interception_cells(player_id, &head);
+        if let Some(dir) = state.first_step(head, current, |p| ahead.contains(&p), |p| !state.is_blocked(p)) {
+            let next = state.next_position(&head, dir);
+            if !state.contested(player_id, next) && state.open_area(next, length + 1) > length {
+                return Some(dir);
+            }
+        }
+        Pathfinder.decide(state, player_id, rng)
+    }
+}
+
+/// Anywhere between Easy and Interceptor, depending on its player's `skill`:
+/// the higher it is, the more it goes for food, the more it hunts, and the further
+/// ahead it checks that a move doesn't box it in. Rooms keep the skill up to date
+/// with a `DifficultyTracker`; elsewhere it plays at `ADAPTIVE_START_SKILL`.
+struct Adaptive;
+
+impl BotStrategy for Adaptive {
+    fn decide(&mut self, state: &GameState, player_id: &str, rng: &mut dyn RngCore) -> Option<Direction> {
+        let me = state.players.get(player_id)?;
+        let (head, current, length) = (*me.snake.head(), me.snake.direction, me.snake.body.len());
+        let skill = me.skill.unwrap_or(ADAPTIVE_START_SKILL).clamp(0.0, 1.0) as f64;
+        let food_seeking = 0.3 + 0.7 * skill; // Easy's 30% up to always
+        let aggression = 0.6 * skill * skill; // Only strong bots go hunting
+        let lookahead = ((skill * ADAPTIVE_MAX_LOOKAHEAD as f64) as usize).clamp(1, length + 1);
+
+        let roomy = |dir: Direction| state.open_area(state.next_position(&head, dir), lookahead) >= lookahead;
+        let safe: Vec<Direction> = valid_turns(current).into_iter().filter(|&dir| roomy(dir)).collect();
+        if safe.is_empty() {
+            return Some(state.roomiest_move(player_id, head, current));
+        }
+
+        if rng.gen_bool(aggression) {
+            let ahead = state.interception_cells(player_id, &head);
+            if let Some(dir) = state.first_step(head, current, |p| ahead.contains(&p), |p| !state.is_blocked(p)) {
+                if safe.contains(&dir) && !state.contested(player_id, state.next_position(&head, dir)) {
+                    return Some(dir);
+                }
+            }
+        }
+        if rng.gen_bool(food_seeking) {
+            if let Some(food) = state.nearest_food(&head) {
+                return safe.iter().copied()
+                    .min_by_key(|&dir| state.distance(&state.next_position(&head, dir), &food));
+            }
+        }
+        Some(safe[rng.gen_range(0..safe.len())])
+    }
+}
+
+/// Monte Carlo lookahead: plays each safe move forward in a sandboxed engine many
+/// times, with every snake following a noisy greedy policy, and keeps the move
+/// whose futures score best. Searches until its share of the tick budget runs out,
+/// and plays greedy when that share is too small to try every move.
+struct Expert {
+    budget: Duration,      // Per decision
+    room_budget: Duration, // Split between every Expert in the room
+    max_rollouts: usize,
+}
+
+impl Default for Expert {
+    fn default() -> Self {
+        Expert { budget: EXPERT_BUDGET, room_budget: EXPERT_ROOM_BUDGET, max_rollouts: EXPERT_MAX_ROLLOUTS }
+    }
+}
+
+impl BotStrategy for Expert {
+    fn decide(&mut self, state: &GameState, player_id: &str, rng: &mut dyn RngCore) -> Option<Direction> {
+        let snake = &state.players.get(player_id)?.snake;
+        let (head, current) = (*snake.head(), snake.direction);
+        let candidates: Vec<Direction> = valid_turns(current).into_iter()
+            .filter(|&dir| state.is_direction_safe(&head, dir))
+            .collect();
+        if candidates.len() <= 1 {
+            return Some(candidates.first().copied().unwrap_or(current));
+        }
+
+        let experts = state.players.values()
+            .filter(|p| p.snake.alive && p.strategy.as_deref() == Some(EXPERT))
+            .count()
+            .max(1);
+        let budget = self.budget.min(self.room_budget / experts as u32);
+        let started = Instant::now();
+
+        // Round-robin so every move gets a fair share of whatever time there is
+        let mut totals = vec![(0.0_f32, 0_u32); candidates.len()];
+        let mut rollouts = 0;
+        while rollouts < self.max_rollouts && started.elapsed() < budget {
+            let i = rollouts % candidates.len();
+            totals[i].0 += rollout(state, player_id, candidates[i], rng);
+            totals[i].1 += 1;
+            rollouts += 1;
+        }
+
+        // The room's budget comes first: with too little time to try every move once,
+        // play the rollout policy's move rather than trust a partial search
+        if rollouts < candidates.len() {
+            return Some(greedy_move(state, player_id, rng));
+        }
+
+        let mean = |(total, count): (f32, u32)| total / count.max(1) as f32;
+        candidates.into_iter()
+            .zip(totals)
+            .max_by(|(_, a), (_, b)| mean(*a).total_cmp(&mean(*b)))
+            .map(|(dir, _)| dir)
+    }
+}
+
+/// Play `first` and then greedy moves for everyone for a few ticks; score how it went for us
+fn rollout(state: &GameState, player_id: &str, first: Direction, rng: &mut dyn RngCore) -> f32 {
+    let mut sim = Engine::sandbox(state, rng.next_u64());
+    let start = &state.players[player_id];
+    let (start_length, start_kills) = (start.snake.body.len(), start.kills);
+
+    let mut first = Some(first);
+    for depth in 0..EXPERT_DEPTH {
+        let inputs: Vec<Input> = sim.state.players.values()
+            .filter(|p| p.snake.alive)
+            .map(|p| {
+                let direction = match first.take().filter(|_| p.id == player_id) {
+                    Some(dir) => dir,
+                    None => greedy_move(&sim.state, &p.id, rng),
+                };
+                Input { player_id: p.id.clone(), direction }
+            })
+            .collect();
+        sim.step(&inputs);
+
+        let me = &sim.state.players[player_id];
+        if !me.snake.alive {
+            return -1000.0 + depth as f32 * 20.0; // Later is less bad
+        }
+        if sim.state.phase != GamePhase::Playing {
+            return 500.0; // Outlasted everyone
+        }
+    }
+
+    let me = &sim.state.players[player_id];
+    let head = *me.snake.head();
+    let length = me.snake.body.len();
+    let room = valid_turns(me.snake.direction).into_iter()
+        .map(|dir| sim.state.open_area(sim.state.next_position(&head, dir), (length * 2).clamp(20, SPACE_CAP)))
+        .max()
+        .unwrap_or(0);
+
+    let mut score = (length - start_length) as f32 * 30.0 + (me.kills - start_kills) as f32 * 100.0;
+    if room < length {
+        score -= 300.0 * (1.0 - room as f32 / length as f32); // Boxed in: dead soon after the horizon
+    }
+    score += room as f32 * 0.5;
+    if let Some(food) = sim.state.nearest_food(&head) {
+        score -= sim.state.distance(&head, &food) as f32;
+    }
+    score
+}
+
+/// Rollout policy: the safe turn closest to food, or now and then any safe turn
+fn greedy_move(state: &GameState, player_id: &str, rng: &mut dyn RngCore) -> Direction {
+    let snake = &state.players[player_id].snake;
+    let (head, current) = (snake.head(), snake.direction);
+    let safe: Vec<Direction> = valid_turns(current).into_iter()
+        .filter(|&dir| state.is_direction_safe(head, dir))
+        .collect();
+    if safe.is_empty() {
+        return current;
+    }
+    if rng.gen_bool(ROLLOUT_NOISE) {
+        return safe[rng.gen_range(0..safe.len())];
+    }
+    let Some(food) = state.nearest_food(head) else {
+        return safe[0];
+    };
+    safe.into_iter()
+        .min_by_key(|&dir| state.distance(&state.next_position(head, dir), &food))
+        .unwrap_or(current)
+}
+
+// =============================================================================
+// SHARED HELPERS
+// =============================================================================
+
+/// Every direction but straight back
+fn valid_turns(current: Direction) -> Vec<Direction> {
+    use Direction::*;
+    match current {
+        Up | Down => vec![Up, Down, Left, Right],
+        Left | Right => vec![Left, Right, Up, Down],
+    }
+    .into_iter()
+    .filter(|d| *d != current.opposite())
+    .collect()
+}
+
+impl GameState {
+    /// Closest food by (wrap-aware) Manhattan distance
+    fn nearest_food(&self, from: &Point) -> Option<Point> {
+        self.food.iter().copied().min_by_key(|food| self.distance(from, food))
+    }
+
+    /// Direction toward target (across an edge when that's shorter)
+    fn direction_toward(&self, from: &Point, to: &Point) -> Direction {
+        let (dx, dy) = self.offset_between(from, to);
+
+        // Prioritize larger difference
+        if dx.abs() > dy.abs() {
+            if dx > 0 { Direction::Right } else { Direction::Left }
+        } else if dy > 0 {
+            Direction::Down
+        } else {
+            Direction::Up
+        }
+    }
+
+    /// Check if direction leads to wall or immediate collision
+    fn is_direction_safe(&self, head: &Point, dir: Direction) -> bool {
+        !self.is_blocked(self.next_position(head, dir))
+    }
+
+    /// Evaluate direction score for the Hard bot
+    fn evaluate_direction(&self, head: &Point, dir: Direction, target: &Point, weights: &HardWeights) -> f32 {
+        let next = self.next_position(head, dir);
+        let mut score = 0.0;
+
+        // Wall and collision penalty
+        if self.is_blocked(next) {
+            return -1000.0;
+        }
+
+        // Distance to food (closer is better)
+        score -= self.distance(target, &next) as f32 * weights.food;
+
+        // Space availability (more open space is better)
+        score += self.open_area(next, HARD_SPACE_CAP) as f32 * weights.space;
+
+        // Power-up proximity bonus
+        for powerup in &self.power_ups {
+            if self.distance(&powerup.position, &next) < 5 {
+                score += weights.power_up;
+            }
+        }
+
+        score
+    }
+
+    /// Open cells reachable from `start` (itself included), counting no further than `cap`
+    fn open_area(&self, start: Point, cap: usize) -> usize {
+        if self.is_blocked(start) {
+            return 0;
+        }
+        let mut visited = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        let mut count = 0;
+        while let Some(pos) = queue.pop_front() {
+            count += 1;
+            if count >= cap {
+                break;
+            }
+            for dir in DIRECTIONS {
+                let next = self.next_position(&pos, dir);
+                if !self.is_blocked(next) && visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        count
+    }
+
+    /// First move of a shortest path from `head` to a cell matching `goal`, walking only
+    /// on `open` cells (through edges and portals). Never starts by reversing.
+    fn first_step(
+        &self,
+        head: Point,
+        current: Direction,
+        goal: impl Fn(Point) -> bool,
+        open: impl Fn(Point) -> bool,
+    ) -> Option<Direction> {
+        let mut visited = HashSet::from([head]);
+        let mut queue = VecDeque::new();
+        for dir in valid_turns(current) {
+            let next = self.next_position(&head, dir);
+            if open(next) && visited.insert(next) {
+                if goal(next) {
+                    return Some(dir);
+                }
+                queue.push_back((next, dir));
+            }
+        }
+        while let Some((pos, first)) = queue.pop_front() {
+            for dir in DIRECTIONS {
+                let next = self.next_position(&pos, dir);
+                if open(next) && visited.insert(next) {
+                    if goal(next) {
+                        return Some(first);
+                    }
+                    queue.push_back((next, first));
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether some `goal` cell can be reached from `start` over `open` cells
+    fn path_exists(&self, start: Point, goal: impl Fn(Point) -> bool, open: impl Fn(Point) -> bool) -> bool {
+        let mut visited = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(pos) = queue.pop_front() {
+            for dir in DIRECTIONS {
+                let next = self.next_position(&pos, dir);
+                if goal(next) {
+                    return true;
+                }
+                if open(next) && visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        false
+    }
+
+    /// Cells the nearest unprotected opponent will cross over the next few ticks if it keeps going straight
+    fn interception_cells(&self, player_id: &str, head: &Point) -> HashSet<Point> {
+        let mut ahead = HashSet::new();
+        let victim = self.players.values()
+            .filter(|p| p.id != player_id && p.snake.alive && !p.is_protected())
+            .min_by_key(|p| self.distance(head, p.snake.head()));
+        if let Some(victim) = victim {
+            let mut cell = *victim.snake.head();
+            for _ in 0..INTERCEPT_LOOKAHEAD {
+                cell = self.next_position(&cell, victim.snake.direction);
+                if self.is_blocked(cell) {
+                    break;
+                }
+                ahead.insert(cell);
+            }
+        }
+        ahead
+    }
+
+    /// An opponent at least as long as us could put its head on `p` next tick
+    fn contested(&self, player_id: &str, p: Point) -> bool {
+        let length = self.players.get(player_id).map_or(0, |me| me.snake.body.len());
+        self.players.values()
+            .filter(|other| other.id != player_id && other.snake.alive && other.snake.body.len() >= length)
+            .any(|other| {
+                valid_turns(other.snake.direction).into_iter()
+                    .any(|dir| self.next_position(other.snake.head(), dir) == p)
+            })
+    }
+
+    /// The safe move with the most room behind it, uncontested cells first
+    fn roomiest_move(&self, player_id: &str, head: Point, current: Direction) -> Direction {
+        valid_turns(current).into_iter()
+            .map(|dir| {
+                let next = self.next_position(&head, dir);
+                (dir, !self.contested(player_id, next), self.open_area(next, SPACE_CAP))
+            })
+            .max_by_key(|&(_, uncontested, area)| (area > 0, uncontested, area))
+            .map_or(current, |(dir, _, _)| dir)
+    }
+}
+
+// =============================================================================
+// ADAPTIVE DIFFICULTY
+// =============================================================================
+
+/// Keeps a room's adaptive bots level with its humans. Finished matches move a
+/// baseline up when the humans beat the bots and down when they lose; during a
+/// match, the score gap, how often each side has died and how long it has lasted
+/// this round nudge skill off it.
+#[derive(Clone, Debug)]
+pub struct DifficultyTracker {
+    rating: f32, // Baseline skill from the matches played so far
+}
+
+impl Default for DifficultyTracker {
+    fn default() -> Self {
+        DifficultyTracker { rating: ADAPTIVE_START_SKILL }
+    }
+}
+
+impl DifficultyTracker {
+    /// Move the baseline by how the humans did in the match that just ended
+    pub fn record_match(&mut self, state: &GameState) {
+        if let Some(result) = human_result(state) {
+            self.rating = (self.rating + RATING_STEP * (2.0 * result - 1.0)).clamp(0.0, 1.0);
+        }
+    }
+
+    /// What adaptive bots should play at right now
+    pub fn skill(&self, state: &GameState) -> f32 {
+        let mut skill = self.rating;
+        if state.phase == GamePhase::Playing {
+            if let Some(lead) = human_lead(state) {
+                skill += IN_MATCH_SWING * lead;
+            }
+        }
+        ((skill * SKILL_STEPS).round() / SKILL_STEPS).clamp(0.0, 1.0)
+    }
+
+    /// Write the current skill into every adaptive bot
+    pub fn apply(&self, state: &mut GameState) {
+        let skill = self.skill(state);
+        for player in state.players.values_mut().filter(|p| p.is_bot && p.strategy.as_deref() == Some(ADAPTIVE)) {
+            player.skill = Some(skill);
+        }
+    }
+}
+
+/// Each signed-in player's adaptive baseline, so it carries over from one game to the next
+#[derive(Default)]
+pub struct DifficultyRatings {
+    trackers: Mutex<HashMap<String, DifficultyTracker>>, // user id -> tracker
+}
+
+impl DifficultyRatings {
+    /// Where this user left off, or a fresh tracker
+    pub fn tracker(&self, user_id: &str) -> DifficultyTracker {
+        self.trackers.lock().unwrap().get(user_id).cloned().unwrap_or_default()
+    }
+
+    pub fn save(&self, user_id: &str, tracker: &DifficultyTracker) {
+        self.trackers.lock().unwrap().insert(user_id.to_string(), tracker.clone());
+    }
+}
+
+/// Humans' share of head-to-heads against the bots over a match: 1 if every human
+/// finished above every bot, 0 if below. None without both humans and bots.
+fn human_result(state: &GameState) -> Option<f32> {
+    let (humans, bots): (Vec<&Player>, Vec<&Player>) = state.players.values().partition(|p| !p.is_bot);
+    if humans.is_empty() || bots.is_empty() {
+        return None;
+    }
+    let rank = |p: &Player| (p.round_wins, p.total_score);
+    let points: f32 = humans.iter()
+        .flat_map(|human| bots.iter().map(move |bot| (rank(human), rank(bot))))
+        .map(|(human, bot)| match human.cmp(&bot) {
+            std::cmp::Ordering::Greater => 1.0,
+            std::cmp::Ordering::Equal => 0.5,
+            std::cmp::Ordering::Less => 0.0,
+        })
+        .sum();
+    Some(points / (humans.len() * bots.len()) as f32)
+}
+
+/// How far ahead the humans are in the current match, from -1 (routed) to 1 (routing):
+/// their best score against the bots' best, their deaths against the bots' average,
+/// and how long their best has lasted this round against the bots' average
+fn human_lead(state: &GameState) -> Option<f32> {
+    let (humans, bots): (Vec<&Player>, Vec<&Player>) = state.players.values().partition(|p| !p.is_bot);
+    if humans.is_empty() || bots.is_empty() {
+        return None;
+    }
+    let best_score = |players: &[&Player]| players.iter().map(|p| p.total_score + p.snake.score).max().unwrap_or(0) as f32;
+    let mean_deaths = |players: &[&Player]| players.iter().map(|p| p.deaths).sum::<u32>() as f32 / players.len() as f32;
+    let score_gap = ((best_score(&humans) - best_score(&bots)) / SCORE_GAP_SCALE).clamp(-1.0, 1.0);
+    let death_gap = ((mean_deaths(&bots) - mean_deaths(&humans)) / DEATH_GAP_SCALE).clamp(-1.0, 1.0);
+
+    // Classic rounds allow one death each, so how soon it came says more than the count
+    let elapsed = state.tick.saturating_sub(state.round_started_at);
+    if elapsed < SURVIVAL_GAP_MIN_TICKS {
+        return Some(0.6 * score_gap + 0.4 * death_gap);
+    }
+    let survived = |p: &&Player| match &p.snake.death {
+        Some(death) if !p.snake.alive => death.tick.saturating_sub(state.round_started_at),
+        _ => elapsed,
+    } as f32;
+    let best_survival = humans.iter().map(survived).fold(0.0, f32::max);
+    let mean_survival = bots.iter().map(survived).sum::<f32>() / bots.len() as f32;
+    let survival_gap = ((best_survival - mean_survival) / elapsed as f32).clamp(-1.0, 1.0);
+    Some(0.5 * score_gap + 0.25 * death_gap + 0.25 * survival_gap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::snake::{player_color, Death, DeathCause, Engine, GamePhase, Player, RoomSettings, Snake};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn bot(id: &str, strategy: &str, body: &[(i32, i32)], direction: Direction) -> Player {
+        let head = Point { x: body[0].0, y: body[0].1 };
+        let mut player = Player::new_bot(id.to_string(), id.to_string(), strategy.to_string(), 0, head, direction);
+        player.snake.body = body.iter().map(|&(x, y)| Point { x, y }).collect();
+        player
+    }
+
+    fn playing(players: Vec<Player>, food: &[(i32, i32)]) -> Engine {
+        let mut engine = Engine::new(1, RoomSettings { power_ups_enabled: false, ..RoomSettings::default() });
+        players.into_iter().for_each(|player| engine.state.add_player(player));
+        engine.state.phase = GamePhase::Playing;
+        engine.state.food = food.iter().map(|&(x, y)| Point { x, y }).collect();
+        engine.state.rebuild_occupancy();
+        engine
+    }
+
+    fn decide(engine: &Engine, id: &str) -> Direction {
+        let strategy = engine.state.players[id].strategy.clone().unwrap();
+        let mut brain = BotRegistry::builtin().create(&strategy).unwrap();
+        brain.decide(&engine.state, id, &mut ChaCha8Rng::seed_from_u64(0)).unwrap()
+    }
+
+    #[test]
+    fn registry_resolves_names_and_every_strategy_plays_a_round() {
+        let registry = Arc::new(BotRegistry::builtin());
+        assert_eq!(registry.resolve("pathfinder"), Some("Pathfinder"));
+        assert!(registry.create("Nope").is_none());
+
+        let names: Vec<String> = registry.list().into_iter().map(|info| info.name).collect();
+        let mut engine = Engine::new(9, RoomSettings { max_players: names.len(), ..RoomSettings::default() });
+        for (slot, name) in names.iter().enumerate() {
+            let (pos, dir) = engine.state.get_spawn_position(slot);
+            let mut player = bot(&format!("bot-{}", slot), name, &[(pos.x, pos.y)], dir);
+            player.snake = Snake::new(pos, dir, player_color(slot));
+            player.slot = slot;
+            engine.state.add_player(player);
+        }
+        engine.start_match(9);
+        let mut brains = BotBrains::new(registry);
+        let mut rng = ChaCha8Rng::seed_from_u64(9);
+        let mut moved = HashSet::new();
+        for _ in 0..300 {
+            let inputs = brains.decide_all(&engine.state, &mut rng);
+            moved.extend(inputs.iter().map(|input| input.player_id.clone()));
+            engine.step(&inputs);
+        }
+        assert_eq!(moved.len(), names.len());
+    }
+
+    #[test]
+    fn pathfinder_skips_food_it_cannot_reach() {
+        // The closer food sits inside another snake's closed loop
+        let ring = [(9, 5), (9, 4), (10, 4), (11, 4), (11, 5), (11, 6), (10, 6), (9, 6)];
+        let players = vec![
+            bot("p", "Pathfinder", &[(10, 10), (9, 10), (8, 10)], Direction::Right),
+            bot("m", "Medium", &[(10, 10), (9, 10), (8, 10)], Direction::Right),
+            bot("w", "Medium", &ring, Direction::Down),
+        ];
+        let mut engine = playing(players, &[(10, 5), (16, 10)]);
+        let medium = engine.state.remove_player("m").unwrap();
+        assert_eq!(decide(&engine, "p"), Direction::Right);
+
+        engine.state.remove_player("p");
+        engine.state.add_player(medium);
+        assert_eq!(decide(&engine, "m"), Direction::Up);
+    }
+
+    /// Food in a two-cell dead end walled off by another snake, right in front of us
+    fn pocket_trap(strategy: &str) -> Engine {
+        let body: Vec<(i32, i32)> = (5..=12).map(|x| (x, 5)).collect();
+        let pocket = [(4, 6), (3, 6), (2, 6), (2, 5), (2, 4), (3, 4), (4, 4)];
+        let players = vec![
+            bot("t", strategy, &body, Direction::Left),
+            bot("w", "Medium", &pocket, Direction::Right),
+        ];
+        playing(players, &[(3, 5)])
+    }
+
+    /// Fixed rollout count and no clock, so the search is deterministic
+    fn deterministic_expert(max_rollouts: usize) -> Expert {
+        Expert { budget: Duration::MAX, room_budget: Duration::MAX, max_rollouts }
+    }
+
+    #[test]
+    fn tail_chaser_skips_food_that_would_trap_it() {
+        assert_ne!(decide(&pocket_trap("TailChaser"), "t"), Direction::Left);
+    }
+
+    #[test]
+    fn expert_sees_the_trap_that_hard_walks_into() {
+        assert_eq!(decide(&pocket_trap("Hard"), "t"), Direction::Left);
+
+        let engine = pocket_trap(EXPERT);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let choice = deterministic_expert(30).decide(&engine.state, "t", &mut rng);
+        assert!(matches!(choice, Some(Direction::Up | Direction::Down)));
+    }
+
+    #[test]
+    fn expert_without_time_to_search_plays_greedy() {
+        let engine = pocket_trap(EXPERT);
+        for seed in 0..10 {
+            let mut crowded = Expert { room_budget: Duration::ZERO, ..Expert::default() };
+            let expected = greedy_move(&engine.state, "t", &mut ChaCha8Rng::seed_from_u64(seed));
+            assert_eq!(crowded.decide(&engine.state, "t", &mut ChaCha8Rng::seed_from_u64(seed)), Some(expected));
+        }
+    }
+
+    #[test]
+    fn adaptive_bots_track_how_the_humans_do() {
+        let mut human = bot("h", "Medium", &[(10, 10), (9, 10), (8, 10)], Direction::Right);
+        human.is_bot = false;
+        let adaptive = bot("a", ADAPTIVE, &[(10, 20), (9, 20), (8, 20)], Direction::Right);
+        let mut engine = playing(vec![human, adaptive], &[(30, 10)]);
+        let mut tracker = DifficultyTracker::default();
+        assert_eq!(tracker.skill(&engine.state), ADAPTIVE_START_SKILL);
+
+        // A human win raises the baseline; a bot running away with this match pulls skill back down
+        engine.state.players.get_mut("h").unwrap().round_wins = 1;
+        tracker.record_match(&engine.state);
+        engine.state.players.get_mut("a").unwrap().snake.score = 50;
+        tracker.apply(&mut engine.state);
+        assert_eq!(engine.state.players["a"].skill, Some(0.5));
+        engine.state.phase = GamePhase::RoundOver;
+        tracker.apply(&mut engine.state);
+        assert_eq!(engine.state.players["a"].skill, Some(0.65));
+        assert_eq!(engine.state.players["h"].skill, None);
+
+        // A human who crashed early is behind even before the bots score
+        engine.state.players.get_mut("a").unwrap().snake.score = 0;
+        engine.state.phase = GamePhase::Playing;
+        engine.state.tick = 100;
+        let human = &mut engine.state.players.get_mut("h").unwrap().snake;
+        human.alive = false;
+        human.death = Some(Death { cause: DeathCause::Wall, killer: None, tick: 10 });
+        assert!(tracker.skill(&engine.state) < 0.65);
+
+        // Full skill looks far enough ahead to stay out of the pocket; none doesn't always
+        let lefts = |skill: f32| {
+            let mut engine = pocket_trap(ADAPTIVE);
+            engine.state.players.get_mut("t").unwrap().skill = Some(skill);
+            (0..20)
+                .filter(|&seed| {
+                    Adaptive.decide(&engine.state, "t", &mut ChaCha8Rng::seed_from_u64(seed)) == Some(Direction::Left)
+                })
+                .count()
+        };
+        assert_eq!(lefts(1.0), 0);
+        assert!(lefts(0.0) > 0);
+    }
+
+    /// Expert vs Hard, one on one, swapping seats every match. Slow in debug builds:
+    /// `cargo test --release expert_beats_hard -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn expert_beats_hard_head_to_head() {
+        let mut registry = BotRegistry::builtin();
+        registry.fix_expert_rollouts(60);
+        let registry = Arc::new(registry);
+
+        let (mut expert_wins, mut hard_wins) = (0, 0);
+        for seed in 0..20_u64 {
+            let seats = if seed % 2 == 0 { [EXPERT, "Hard"] } else { ["Hard", EXPERT] };
+            let mut engine = Engine::new(seed, RoomSettings { max_players: 2, rounds: 1, ..RoomSettings::default() });
+            for (slot, strategy) in seats.iter().enumerate() {
+                let (pos, dir) = engine.state.get_spawn_position(slot);
+                let mut player = bot(strategy, strategy, &[(pos.x, pos.y)], dir);
+                player.snake = Snake::new(pos, dir, player_color(slot));
+                player.slot = slot;
+                engine.state.add_player(player);
+            }
+            engine.start_match(seed);
+
+            let mut brains = BotBrains::new(registry.clone());
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            while engine.state.phase != GamePhase::MatchOver && engine.state.tick < 3_000 {
+                let inputs = brains.decide_all(&engine.state, &mut rng);
+                engine.step(&inputs);
+            }
+            match engine.state.winner.as_deref() {
+                Some(EXPERT) => expert_wins += 1,
+                Some("Hard") => hard_wins += 1,
+                _ => {}
+            }
+        }
+        assert!(expert_wins > hard_wins * 2, "Expert {} - {} Hard", expert_wins, hard_wins);
+    }
+}
@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use rand::RngCore;
+
+use super::bots::BotStrategy;
+use super::store::JsonDirStore;
+use super::{Direction, GameState};
+use crate::code_runner::{self, BotPermit, BotProcess};
+
+pub const MAX_BOT_NAME_LEN: usize = 40;
+const CODE_BOT_DEADLINE: Duration = Duration::from_millis(25); // Wait for answers each tick, shared by a room's programs
+const STRATEGY_PREFIX: &str = "code:";
+
+// =============================================================================
+// SUBMITTED PROGRAMS
+// =============================================================================
+
+/// A user's bot program, in one of the code runner's languages
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BotSource {
+    pub id: String,
+    pub name: String,
+    pub language: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>, // User id of the uploader
+}
+
+/// A program without its code, for bot pickers
+#[derive(Clone, Debug, Serialize)]
+pub struct BotSourceInfo {
+    pub id: String,
+    pub name: String,
+    pub language: String,
+    pub author: Option<String>,
+    pub strategy: String, // Name to seat it with in a bot arena
+}
+
+impl BotSource {
+    /// Same checks the code runner applies before running anything
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_BOT_NAME_LEN {
+            return Err(format!("Bot names must be 1 to {} characters", MAX_BOT_NAME_LEN));
+        }
+        if !code_runner::LANGUAGES.contains(&self.language.as_str()) {
+            return Err(format!(
+                "Language '{}' is not supported. Supported: {}",
+                self.language, code_runner::LANGUAGES.join(", ")
+            ));
+        }
+        if self.code.trim().is_empty() {
+            return Err("Bot code is empty".to_string());
+        }
+        code_runner::check_security(&self.language, &self.code)
+    }
+
+    /// The bot's name in a room's `BotRegistry`
+    pub fn strategy(&self) -> String {
+        format!("{}{}", STRATEGY_PREFIX, self.id)
+    }
+
+    pub fn info(&self) -> BotSourceInfo {
+        BotSourceInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            language: self.language.clone(),
+            author: self.author.clone(),
+            strategy: self.strategy(),
+        }
+    }
+}
+
+// =============================================================================
+// STORAGE
+// =============================================================================
+
+/// Where submitted programs live; the filesystem store is the default
+pub trait BotSourceStore: Send + Sync {
+    fn save(&self, source: &BotSource) -> io::Result<()>;
+    fn load(&self, id: &str) -> io::Result<Option<BotSource>>;
+    fn list(&self) -> io::Result<Vec<BotSource>>;
+    fn delete(&self, id: &str) -> io::Result<bool>; // False if there was nothing to delete
+}
+
+/// One JSON file per program under `dir`
+pub type FileBotSourceStore = JsonDirStore<BotSource>;
+
+impl FileBotSourceStore {
+    /// Reads CODE_BOT_DIR, defaulting to `./user_bots`
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("CODE_BOT_DIR").unwrap_or_else(|_| "user_bots".to_string()))
+    }
+}
+
+impl BotSourceStore for FileBotSourceStore {
+    fn save(&self, source: &BotSource) -> io::Result<()> {
+        self.write(&source.id, source)
+    }
+
+    fn load(&self, id: &str) -> io::Result<Option<BotSource>> {
+        self.read(id)
+    }
+
+    fn list(&self) -> io::Result<Vec<BotSource>> {
+        let mut sources = self.read_all()?;
+        sources.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        Ok(sources)
+    }
+
+    fn delete(&self, id: &str) -> io::Result<bool> {
+        self.remove(id)
+    }
+}
+
+// =============================================================================
+// STRATEGY
+// =============================================================================
+
+/// What the program is told each tick
+#[derive(Serialize)]
+struct TickMessage<'a> {
+    you: &'a str, // The bot's own player id
+    state: super::GameStateView,
+}
+
+enum Program {
+    Starting(JoinHandle<Result<BotProcess, String>>), // Java is still compiling, say
+    Running(BotProcess),
+    Stopped,
+}
+
+/// Steers with a submitted program. It's sent the game state as one JSON line per
+/// tick and has until the deadline to print a direction; otherwise the snake keeps going.
+pub struct CodeBot {
+    name: String,
+    program: Program,
+    deadline: Option<Instant>, // Set when this tick's state went out
+}
+
+impl CodeBot {
+    /// Starts the program in the background, in the slot `permit` holds, so compiling
+    /// never holds up a room. Without a slot it never starts and the snake goes straight.
+    pub fn start(source: &BotSource, permit: Option<BotPermit>) -> Self {
+        let (language, code) = (source.language.clone(), source.code.clone());
+        let program = match permit {
+            Some(permit) => Program::Starting(thread::spawn(move || BotProcess::spawn(&language, &code, permit))),
+            None => {
+                log::warn!("Bot '{}' has no process slot to run in", source.name);
+                Program::Stopped
+            }
+        };
+        CodeBot { name: source.name.clone(), program, deadline: None }
+    }
+
+    fn running(&mut self) -> Option<&mut BotProcess> {
+        if let Program::Starting(handle) = &self.program {
+            if !handle.is_finished() {
+                return None;
+            }
+            let Program::Starting(handle) = std::mem::replace(&mut self.program, Program::Stopped) else {
+                return None;
+            };
+            match handle.join() {
+                Ok(Ok(process)) => self.program = Program::Running(process),
+                Ok(Err(e)) => log::warn!("Bot '{}' failed to start: {}", self.name, e),
+                Err(_) => log::warn!("Bot '{}' failed to start", self.name),
+            }
+        }
+        if let Program::Running(process) = &mut self.program {
+            if process.has_exited() {
+                log::info!("Bot '{}' exited", self.name);
+                self.program = Program::Stopped;
+            }
+        }
+        match &mut self.program {
+            Program::Running(process) => Some(process),
+            _ => None,
+        }
+    }
+}
+
+impl BotStrategy for CodeBot {
+    fn prepare(&mut self, state: &GameState, player_id: &str) {
+        self.deadline = None;
+        let Some(process) = self.running() else {
+            return;
+        };
+        let message = TickMessage { you: player_id, state: state.view() };
+        match serde_json::to_string(&message) {
+            Ok(line) => {
+                // A program still chewing on earlier states skips this one
+                if process.send(line) {
+                    self.deadline = Some(Instant::now() + CODE_BOT_DEADLINE);
+                }
+            }
+            Err(e) => log::error!("Failed to encode state for bot '{}': {}", self.name, e),
+        }
+    }
+
+    fn decide(&mut self, _state: &GameState, _player_id: &str, _rng: &mut dyn RngCore) -> Option<Direction> {
+        let deadline = self.deadline.take()?;
+        let Program::Running(process) = &mut self.program else {
+            return None;
+        };
+        parse_direction(&process.reply(deadline)?)
+    }
+}
+
+/// `Up`, `"left"`, or `{"direction": "Down"}`, in any case
+fn parse_direction(reply: &str) -> Option<Direction> {
+    let reply = reply.trim();
+    let word = match serde_json::from_str::<serde_json::Value>(reply) {
+        Ok(serde_json::Value::Object(object)) => object.get("direction")?.as_str()?.to_string(),
+        Ok(serde_json::Value::String(word)) => word,
+        _ => reply.to_string(),
+    };
+    match word.to_ascii_lowercase().as_str() {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_runner::BotProcessLimits;
+    use crate::games::snake::{BotBrains, BotRegistry, Engine, GamePhase, Player, RoomSettings};
+    use std::sync::Arc;
+
+    fn source(language: &str, code: &str) -> BotSource {
+        BotSource {
+            id: "b0t".to_string(),
+            name: "Lefty".to_string(),
+            language: language.to_string(),
+            code: code.to_string(),
+            author: None,
+        }
+    }
+
+    #[test]
+    fn replies_parse_leniently_and_uploads_get_the_runner_checks() {
+        assert_eq!(parse_direction("Up"), Some(Direction::Up));
+        assert_eq!(parse_direction(" \"left\" "), Some(Direction::Left));
+        assert_eq!(parse_direction(r#"{"direction": "DOWN"}"#), Some(Direction::Down));
+        assert_eq!(parse_direction("north"), None);
+
+        assert!(source("python", "print('Up')").validate().is_ok());
+        assert!(source("python", "import os").validate().is_err());
+        assert!(source("ruby", "puts 'Up'").validate().is_err());
+        assert!(BotSource { name: " ".to_string(), ..source("python", "print('Up')") }.validate().is_err());
+    }
+
+    #[test]
+    fn bot_processes_are_capped_overall_and_per_user() {
+        let limits = Arc::new(BotProcessLimits::new(3, 2));
+        let first = limits.acquire("ann").unwrap();
+        let _second = limits.acquire("ann").unwrap();
+        assert_eq!(limits.available("ann"), 0);
+        assert!(limits.acquire("ann").is_err());
+
+        let _third = limits.acquire("bob").unwrap();
+        assert_eq!(limits.available("bob"), 0);
+        assert!(limits.acquire("cat").is_err());
+
+        drop(first);
+        assert_eq!(limits.available("ann"), 1);
+        assert!(limits.acquire("cat").is_ok());
+    }
+
+    #[test]
+    fn registered_programs_hold_their_slots_until_the_room_lets_go() {
+        let limits = Arc::new(BotProcessLimits::new(4, 2));
+        let mut registry = BotRegistry::builtin();
+        registry.register_code_bot(&source("python", "print('Up')"), vec![limits.acquire("ann").unwrap()]);
+        assert_eq!(limits.available("ann"), 1);
+        drop(registry);
+        assert_eq!(limits.available("ann"), 2);
+    }
+
+    /// Needs a JDK on the PATH
+    #[test]
+    #[ignore]
+    fn java_compiles_under_the_bot_limits_and_failures_give_back_the_slot() {
+        let limits = Arc::new(BotProcessLimits::new(1, 1));
+        let error = BotProcess::spawn("java", "class Bot { oops }", limits.acquire("ann").unwrap()).err().unwrap();
+        assert!(error.contains("error"), "{}", error);
+        assert_eq!(limits.available("ann"), 1);
+
+        let code = "class Bot { public static void main(String[] args) { System.out.println(\"Up\"); } }";
+        let mut process = BotProcess::spawn("java", code, limits.acquire("ann").unwrap()).unwrap();
+        assert!(process.send("{}".to_string()));
+        assert_eq!(process.reply(Instant::now() + Duration::from_secs(10)).as_deref(), Some("Up"));
+    }
+
+    /// Needs python3 on the PATH
+    #[test]
+    #[ignore]
+    fn a_python_program_steers_its_snake() {
+        let code = "import json\nwhile True:\n    msg = json.loads(input())\n    print('Left' if msg['state']['tick'] > 2 else 'Down')\n";
+        let bot = source("python", code);
+        let mut registry = BotRegistry::builtin();
+        registry.register_code_bot(&bot, vec![Arc::new(BotProcessLimits::new(1, 1)).acquire("tester").unwrap()]);
+
+        let mut engine = Engine::new(1, RoomSettings { max_players: 1, power_ups_enabled: false, ..RoomSettings::default() });
+        let (pos, dir) = engine.state.get_spawn_position(0);
+        engine.add_player(Player::new_bot("p".to_string(), bot.name.clone(), bot.strategy(), 0, pos, dir));
+        let mut brains = BotBrains::new(Arc::new(registry));
+        brains.warm_up(&engine.state);
+        engine.start_match(1);
+        while engine.state.phase != GamePhase::Playing {
+            engine.step(&[]);
+        }
+        std::thread::sleep(Duration::from_millis(500)); // Interpreter start-up
+
+        let mut rng = rand::thread_rng();
+        let mut answers = Vec::new();
+        for _ in 0..5 {
+            let inputs = brains.decide_all(&engine.state, &mut rng);
+            answers.extend(inputs.iter().map(|input| input.direction));
+            engine.step(&inputs);
+        }
+        assert!(answers.contains(&Direction::Down) && answers.contains(&Direction::Left), "{:?}", answers);
+    }
+}
@@ -0,0 +1,972 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{
+    player_color, ActivePowerUp, Death, DeathCause, Direction, GameMap, GameMode, GamePhase, GameState,
+    HeadOnRule, Player, Point, PowerUp, PowerUpType, RoomSettings, Snake, Standing,
+    ARENA_FOOD_PER_PLAYER, ARENA_RESPAWN_DELAY, ARENA_ROUND_DURATION, COUNTDOWN_DURATION,
+    INITIAL_SNAKE_LENGTH, INTERMISSION_DURATION, POWERUP_GHOST_DURATION,
+    POWERUP_SHIELD_DURATION, POWERUP_SPAWN_INTERVAL, POWERUP_SPEED_DURATION,
+};
+
+const INITIAL_FOOD: usize = 3;
+const SUBSTEPS_PER_TICK: u32 = 2; // Finest movement resolution; a pace of 2 moves every sub-step
+const SPAWN_CANDIDATES: usize = 24; // Free cells tried when placing an arena snake
+const SPAWN_CLEARANCE: i32 = 5;     // Open cells required ahead of a fresh arena snake
+
+fn in_bounds(p: Point, width: i32, height: i32) -> bool {
+    p.x >= 0 && p.x < width && p.y >= 0 && p.y < height
+}
+
+/// `p` moved `n` cells towards `dir`
+fn offset(p: Point, dir: Direction, n: i32) -> Point {
+    match dir {
+        Direction::Up => Point { x: p.x, y: p.y - n },
+        Direction::Down => Point { x: p.x, y: p.y + n },
+        Direction::Left => Point { x: p.x - n, y: p.y },
+        Direction::Right => Point { x: p.x + n, y: p.y },
+    }
+}
+
+// =============================================================================
+// INPUTS & EVENTS
+// =============================================================================
+
+/// A direction change, applied in arrival order at the start of the next step
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Input {
+    pub player_id: String,
+    pub direction: Direction,
+}
+
+/// Something that happened during a step, for the room (or any other driver) to react to
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", content = "payload")]
+pub enum GameEvent {
+    RoundStarted { round: u8 },   // Board reset, countdown begins
+    Countdown { remaining: u8 },  // Countdown or intermission ticked down
+    GameStarted,
+    FoodEaten { player_id: String, at: Point },
+    FoodSpawned { at: Point },
+    PowerUpCollected { player_id: String, power_up_id: String, power_type: PowerUpType },
+    PowerUpSpawned { power_up: PowerUp },
+    PlayerDied { player_id: String, cause: DeathCause, killer: Option<String> },
+    PlayerRespawned { player_id: String, at: Point }, // Arena only
+    RoundOver { round: u8, winner: Option<String>, standings: Vec<Standing> },
+    MatchOver { winner: Option<String>, standings: Vec<Standing> },
+}
+
+// =============================================================================
+// ENGINE
+// =============================================================================
+
+/// The snake rules without sockets, timers or I/O. The same seed, settings,
+/// roster and inputs always produce the same states and events.
+#[derive(Clone, Debug)]
+pub struct Engine {
+    pub state: GameState,
+    pub settings: RoomSettings,
+    rng: ChaCha8Rng,
+    powerup_spawn_ticks: u32,
+    next_power_up_id: u64,
+}
+
+impl Engine {
+    pub fn new(seed: u64, settings: RoomSettings) -> Self {
+        let mut state = GameState::new();
+        state.mode = settings.mode;
+        state.wrap_edges = settings.wrap_edges;
+        state.head_on = settings.head_on;
+
+        let mut engine = Engine {
+            state,
+            settings,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            powerup_spawn_ticks: 0,
+            next_power_up_id: 0,
+        };
+        engine.set_map(None);
+        engine
+    }
+
+    /// A throwaway copy of `state` for bots to search ahead in. It has its own randomness,
+    /// so it can't reveal where the real game puts food next, and it never spawns power-ups.
+    pub fn sandbox(state: &GameState, seed: u64) -> Self {
+        let settings = RoomSettings {
+            mode: state.mode,
+            wrap_edges: state.wrap_edges,
+            head_on: state.head_on,
+            power_ups_enabled: false,
+            ..RoomSettings::default()
+        };
+        Engine {
+            state: state.clone(),
+            settings,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            powerup_spawn_ticks: 0,
+            next_power_up_id: 0,
+        }
+    }
+
+    /// Lay out the board: the map's size and terrain, or the open board from the settings
+    pub fn set_map(&mut self, map: Option<Arc<GameMap>>) {
+        let (width, height) = match &map {
+            Some(map) => (map.width, map.height),
+            None => self.settings.dimensions(),
+        };
+        self.state.grid_width = width;
+        self.state.grid_height = height;
+        self.state.map = map;
+        self.state.rebuild_occupancy();
+    }
+
+    /// Reseed, clear the scoreboard and begin round 1 with the current roster
+    pub fn start_match(&mut self, seed: u64) -> Vec<GameEvent> {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self.next_power_up_id = 0;
+
+        for player in self.state.players.values_mut() {
+            player.round_wins = 0;
+            player.total_score = 0;
+            player.kills = 0;
+            player.deaths = 0;
+        }
+        self.state.tick = 0;
+        self.state.round = 1;
+        self.state.total_rounds = self.settings.rounds;
+
+        let mut events = Vec::new();
+        self.start_round(&mut events);
+        events
+    }
+
+    /// Drop back to the lobby, e.g. once every player has left
+    pub fn reset(&mut self) {
+        self.state.phase = GamePhase::Lobby;
+        self.state.food.clear();
+        self.state.power_ups.clear();
+        self.state.winner = None;
+        self.state.countdown = 0;
+        self.state.round = 0;
+        self.state.ends_at_tick = None;
+        self.powerup_spawn_ticks = 0;
+        self.state.rebuild_occupancy();
+    }
+
+    /// Seat a player. Arenas that are already running drop them somewhere open.
+    pub fn add_player(&mut self, mut player: Player) {
+        if self.settings.mode == GameMode::Arena && self.state.phase != GamePhase::Lobby {
+            self.state.remove_player(&player.id);
+            let (pos, dir) = self.find_spawn(player.slot);
+            player.snake = Snake::new(pos, dir, player.snake.color.clone());
+        }
+        self.state.add_player(player);
+    }
+
+    /// Advance the simulation by one tick
+    pub fn step(&mut self, inputs: &[Input]) -> Vec<GameEvent> {
+        for input in inputs {
+            if let Some(player) = self.state.players.get_mut(&input.player_id) {
+                player.snake.set_direction(input.direction);
+            }
+        }
+
+        let mut events = Vec::new();
+        match self.state.phase {
+            GamePhase::Countdown => self.step_countdown(&mut events),
+            GamePhase::RoundOver => self.step_intermission(&mut events),
+            GamePhase::Playing => self.step_playing(&mut events),
+            GamePhase::Lobby | GamePhase::MatchOver => {}
+        }
+        events
+    }
+
+    fn ticks_per_second(&self) -> u32 {
+        self.settings.speed.ticks_for(Duration::from_secs(1))
+    }
+
+    fn step_countdown(&mut self, events: &mut Vec<GameEvent>) {
+        if self.state.countdown == 0 {
+            return;
+        }
+
+        self.state.countdown_ticks += 1;
+        if self.state.countdown_ticks >= self.ticks_per_second() {
+            self.state.countdown -= 1;
+            self.state.countdown_ticks = 0;
+            events.push(GameEvent::Countdown { remaining: self.state.countdown });
+        }
+
+        if self.state.countdown == 0 {
+            self.state.phase = GamePhase::Playing;
+            self.state.round_started_at = self.state.tick;
+            events.push(GameEvent::GameStarted);
+        }
+    }
+
+    fn step_intermission(&mut self, events: &mut Vec<GameEvent>) {
+        self.state.countdown_ticks += 1;
+        if self.state.countdown_ticks < self.ticks_per_second() {
+            return;
+        }
+
+        self.state.countdown = self.state.countdown.saturating_sub(1);
+        self.state.countdown_ticks = 0;
+        if self.state.countdown == 0 {
+            self.state.round += 1;
+            self.start_round(events);
+        } else {
+            events.push(GameEvent::Countdown { remaining: self.state.countdown });
+        }
+    }
+
+    fn step_playing(&mut self, events: &mut Vec<GameEvent>) {
+        self.state.tick += 1;
+
+        if self.settings.mode == GameMode::Arena {
+            self.respawn_dead(events);
+        }
+
+        if self.settings.power_ups_enabled {
+            self.powerup_spawn_ticks += 1;
+            if self.powerup_spawn_ticks >= self.settings.speed.ticks_for(POWERUP_SPAWN_INTERVAL) {
+                self.powerup_spawn_ticks = 0;
+                self.spawn_power_up(events);
+            }
+        }
+
+        // Faster snakes move several times per tick; every move is fully resolved
+        // (collisions, food, power-ups) before the next sub-step
+        for substep in 0..SUBSTEPS_PER_TICK {
+            let movers = self.move_snakes(substep);
+            if movers.is_empty() {
+                continue;
+            }
+            self.check_collisions(&movers, events);
+            self.eat_food(&movers, events);
+            self.collect_power_ups(&movers, events);
+        }
+
+        // Update active power-ups (decrement timers)
+        for player in self.state.players.values_mut() {
+            if let Some(ref mut active_power) = player.active_power {
+                active_power.ticks_remaining = active_power.ticks_remaining.saturating_sub(1);
+                if active_power.ticks_remaining == 0 {
+                    player.active_power = None;
+                }
+            }
+        }
+
+        // Check round over: arenas run on the clock, classic to the last snake standing
+        let alive_count = self.state.players.values().filter(|p| p.snake.alive).count();
+        let total_players = self.state.players.len();
+        let round_over = match self.settings.mode {
+            GameMode::Arena => self.state.ends_at_tick.is_some_and(|end| self.state.tick >= end),
+            GameMode::Classic => (total_players > 1 && alive_count <= 1) || (total_players == 1 && alive_count == 0),
+        };
+
+        if round_over {
+            self.end_round(events);
+        }
+    }
+
+    /// Bring arena snakes back once their respawn delay is up; they keep their score
+    fn respawn_dead(&mut self, events: &mut Vec<GameEvent>) {
+        let delay = self.settings.speed.ticks_for(ARENA_RESPAWN_DELAY) as u64;
+        let tick = self.state.tick;
+        let due: Vec<String> = self.state.players.values()
+            .filter(|p| !p.snake.alive && p.snake.death.as_ref().is_some_and(|d| d.tick + delay <= tick))
+            .map(|p| p.id.clone())
+            .collect();
+
+        for id in due {
+            let Some(player) = self.state.players.get(&id) else {
+                continue;
+            };
+            let slot = player.slot;
+            for &cell in &player.snake.body {
+                self.state.occupancy.remove_snake(cell);
+            }
+
+            let (pos, dir) = self.find_spawn(slot);
+            let Some(player) = self.state.players.get_mut(&id) else {
+                continue;
+            };
+            let score = player.snake.score;
+            player.snake = Snake::new(pos, dir, player.snake.color.clone());
+            player.snake.score = score;
+            player.active_power = None;
+            for &cell in &player.snake.body {
+                self.state.occupancy.add_snake(cell);
+            }
+            events.push(GameEvent::PlayerRespawned { player_id: id, at: pos });
+        }
+    }
+
+    /// Open spot for a new arena snake, as far as possible from the other heads
+    fn find_spawn(&mut self, slot: usize) -> (Point, Direction) {
+        let heads: Vec<Point> = self.state.players.values()
+            .filter(|p| p.snake.alive && !p.snake.body.is_empty())
+            .map(|p| *p.snake.head())
+            .collect();
+        let center = Point { x: self.state.grid_width / 2, y: self.state.grid_height / 2 };
+
+        let mut best: Option<(i32, Point, Direction)> = None;
+        for _ in 0..SPAWN_CANDIDATES {
+            let Some(head) = self.state.occupancy.random_free(&mut self.rng) else {
+                break;
+            };
+            // Face the middle of the board where possible
+            let (dx, dy) = (center.x - head.x, center.y - head.y);
+            let horizontal = if dx >= 0 { Direction::Right } else { Direction::Left };
+            let vertical = if dy >= 0 { Direction::Down } else { Direction::Up };
+            let preferred = if dx.abs() >= dy.abs() {
+                [horizontal, vertical, vertical.opposite(), horizontal.opposite()]
+            } else {
+                [vertical, horizontal, horizontal.opposite(), vertical.opposite()]
+            };
+            let Some(dir) = preferred.into_iter().find(|&dir| self.spawn_fits(head, dir)) else {
+                continue;
+            };
+
+            let distance = heads.iter()
+                .map(|h| self.state.distance(h, &head))
+                .min()
+                .unwrap_or(i32::MAX);
+            if best.is_none_or(|(best_distance, _, _)| distance > best_distance) {
+                best = Some((distance, head, dir));
+            }
+        }
+
+        match best {
+            Some((_, head, dir)) => (head, dir),
+            None => self.state.get_spawn_position(slot), // Packed board: fall back to the seat
+        }
+    }
+
+    /// A fresh snake's body fits on empty cells with open space ahead of it
+    fn spawn_fits(&self, head: Point, dir: Direction) -> bool {
+        let body_clear = (0..INITIAL_SNAKE_LENGTH as i32)
+            .all(|i| self.state.occupancy.is_free(self.state.wrap(offset(head, dir.opposite(), i))));
+        let ahead_clear = (1..=SPAWN_CLEARANCE)
+            .all(|i| !self.state.is_blocked(offset(head, dir, i)));
+        body_clear && ahead_clear
+    }
+
+    /// Food kept on the board: a few in classic, scaled to the crowd in arenas
+    fn food_target(&self) -> usize {
+        match self.settings.mode {
+            GameMode::Classic => INITIAL_FOOD,
+            GameMode::Arena => INITIAL_FOOD.max(self.state.players.len() * ARENA_FOOD_PER_PLAYER),
+        }
+    }
+
+    fn top_up_food(&mut self, events: &mut Vec<GameEvent>) {
+        let missing = self.food_target().saturating_sub(self.state.food.len());
+        for _ in 0..missing {
+            self.spawn_food(events);
+        }
+    }
+
+    /// Advance every alive snake whose pace includes this sub-step; returns who moved
+    fn move_snakes(&mut self, substep: u32) -> Vec<String> {
+        let mut movers = Vec::new();
+        for player in self.state.players.values_mut() {
+            let pace = player.pace().min(SUBSTEPS_PER_TICK);
+            // Spread `pace` moves evenly over the tick, normal speed moving on the last sub-step
+            let moves_now = (substep + 1) * pace / SUBSTEPS_PER_TICK > substep * pace / SUBSTEPS_PER_TICK;
+            if player.snake.alive && moves_now {
+                if let Some(tail) = player.snake.move_forward() {
+                    self.state.occupancy.remove_snake(tail);
+                }
+                self.state.occupancy.add_snake(player.snake.body[0]);
+                movers.push(player.id.clone());
+            }
+        }
+        movers
+    }
+
+    /// Judge every mover against the same post-move snapshot, then apply all deaths
+    /// at once, so the outcome never depends on player order
+    fn check_collisions(&mut self, movers: &[String], events: &mut Vec<GameEvent>) {
+        // Ghosts (or everyone, on a wrapped board) come back in on the far edge,
+        // and portals teleport, before anything is judged
+        let (grid_width, grid_height) = (self.state.grid_width, self.state.grid_height);
+        for id in movers {
+            let Some(player) = self.state.players.get(id) else {
+                continue;
+            };
+            let is_ghost = player.active_power.as_ref().is_some_and(|p| p.power_type == PowerUpType::Ghost);
+            let head = player.snake.body[0];
+            let mut moved_to = head;
+            if (is_ghost || self.state.wrap_edges) && !in_bounds(head, grid_width, grid_height) {
+                moved_to = Point {
+                    x: head.x.rem_euclid(grid_width),
+                    y: head.y.rem_euclid(grid_height),
+                };
+            }
+            if let Some(exit) = self.state.portal_exit(moved_to) {
+                moved_to = exit;
+            }
+            if moved_to != head {
+                self.state.occupancy.remove_snake(head);
+                self.state.occupancy.add_snake(moved_to);
+                if let Some(player) = self.state.players.get_mut(id) {
+                    player.snake.body[0] = moved_to;
+                }
+            }
+        }
+
+        let deaths: Vec<(String, DeathCause, Option<String>)> = movers.iter()
+            .filter_map(|id| {
+                let (cause, killer) = self.collision_for(id, movers)?;
+                Some((id.clone(), cause, killer))
+            })
+            .collect();
+
+        let tick = self.state.tick;
+        for (id, cause, killer) in deaths {
+            if let Some(player) = self.state.players.get_mut(&id) {
+                player.snake.alive = false;
+                player.snake.death = Some(Death { cause, killer: killer.clone(), tick });
+                player.deaths += 1;
+            }
+            if let Some(killer) = killer.as_ref().and_then(|k| self.state.players.get_mut(k)) {
+                killer.kills += 1;
+            }
+            events.push(GameEvent::PlayerDied { player_id: id, cause, killer });
+        }
+    }
+
+    /// Why `id` dies this sub-step, if it does. Reads state only.
+    fn collision_for(&self, id: &str, movers: &[String]) -> Option<(DeathCause, Option<String>)> {
+        let player = &self.state.players[id];
+        if !player.snake.alive {
+            return None;
+        }
+        let head = *player.snake.head();
+
+        // Ghosts have already wrapped back in, so anyone still off the board hit the edge
+        if !in_bounds(head, self.state.grid_width, self.state.grid_height) {
+            return Some((DeathCause::Wall, None));
+        }
+
+        // Ghosts pass through map walls; shields only guard against snakes
+        if self.state.occupancy.is_wall(head) {
+            let is_ghost = player.active_power.as_ref().is_some_and(|p| p.power_type == PowerUpType::Ghost);
+            return (!is_ghost).then_some((DeathCause::Wall, None));
+        }
+
+        // Shield and Ghost both protect against every snake collision
+        if player.is_protected() {
+            return None;
+        }
+
+        // Our own head is the only segment on the cell: nothing to hit but other heads
+        let crowded = self.state.occupancy.snakes_at(head) > 1;
+        if crowded && player.snake.body.iter().skip(1).any(|p| *p == head) {
+            return Some((DeathCause::SelfCollision, None));
+        }
+
+        // Heads meeting on one cell, or two snakes swapping cells, are head-on
+        let neck = player.snake.body.get(1).copied();
+        let mut partners = Vec::new();
+        for other_id in movers.iter().filter(|other| other.as_str() != id) {
+            let other = &self.state.players[other_id];
+            let other_head = *other.snake.head();
+            let swapped = neck == Some(other_head) && other.snake.body.get(1) == Some(&head);
+            if other.snake.alive && (other_head == head || swapped) {
+                partners.push(other_id.as_str());
+            }
+        }
+        for other_id in &partners {
+            let other = &self.state.players[*other_id];
+            let survives = self.settings.head_on == HeadOnRule::LongerWins
+                && player.snake.body.len() > other.snake.body.len();
+            if !survives {
+                return Some((DeathCause::HeadOn, Some(other_id.to_string())));
+            }
+        }
+
+        if !crowded {
+            return None;
+        }
+
+        // Bodies of other snakes, dead ones included, are obstacles
+        self.state.players.values()
+            .filter(|other| other.id != id && !partners.contains(&other.id.as_str()))
+            .find(|other| other.snake.body.contains(&head))
+            .map(|other| (DeathCause::Body, Some(other.id.clone())))
+    }
+
+    /// Food under a surviving head is eaten; snakes arriving together share it
+    fn eat_food(&mut self, movers: &[String], events: &mut Vec<GameEvent>) {
+        let eaters: Vec<(String, Point)> = movers.iter()
+            .map(|id| &self.state.players[id])
+            .filter(|p| p.snake.alive && self.state.food.contains(p.snake.head()))
+            .map(|p| (p.id.clone(), *p.snake.head()))
+            .collect();
+
+        for (id, at) in &eaters {
+            self.grow(id, 1);
+            events.push(GameEvent::FoodEaten { player_id: id.clone(), at: *at });
+        }
+
+        self.state.food.retain(|f| !eaters.iter().any(|(_, at)| at == f));
+        for (_, at) in &eaters {
+            self.state.occupancy.set_item(*at, false);
+        }
+
+        // Spawn new food if eaten
+        if !eaters.is_empty() {
+            self.top_up_food(events);
+        }
+    }
+
+    /// Power-ups under a surviving head are collected; snakes arriving together all get it
+    fn collect_power_ups(&mut self, movers: &[String], events: &mut Vec<GameEvent>) {
+        let speed = self.settings.speed.clone();
+        let collected: Vec<(String, PowerUp)> = movers.iter()
+            .map(|id| &self.state.players[id])
+            .filter(|p| p.snake.alive)
+            .filter_map(|p| {
+                let powerup = self.state.power_ups.iter().find(|pu| pu.position == *p.snake.head())?;
+                Some((p.id.clone(), powerup.clone()))
+            })
+            .collect();
+
+        for (id, powerup) in &collected {
+            events.push(GameEvent::PowerUpCollected {
+                player_id: id.clone(),
+                power_up_id: powerup.id.clone(),
+                power_type: powerup.power_type.clone(),
+            });
+
+            // Apply power-up effect
+            let duration = match powerup.power_type {
+                PowerUpType::Grow => {
+                    // Instant effect: grow +5 segments
+                    self.grow(id, 5);
+                    continue;
+                },
+                PowerUpType::SpeedBoost => POWERUP_SPEED_DURATION,
+                PowerUpType::Shield => POWERUP_SHIELD_DURATION,
+                PowerUpType::Ghost => POWERUP_GHOST_DURATION,
+            };
+            let Some(player) = self.state.players.get_mut(id) else {
+                continue;
+            };
+            player.active_power = Some(ActivePowerUp {
+                power_type: powerup.power_type.clone(),
+                ticks_remaining: speed.ticks_for(duration),
+            });
+        }
+
+        // Remove collected power-ups
+        self.state.power_ups.retain(|pu| !collected.iter().any(|(_, taken)| taken.id == pu.id));
+        for (_, taken) in &collected {
+            self.state.occupancy.set_item(taken.position, false);
+        }
+    }
+
+    /// Lengthen a snake at its tail, keeping the grid in step
+    fn grow(&mut self, id: &str, segments: usize) {
+        let Some(player) = self.state.players.get_mut(id) else {
+            return;
+        };
+        for _ in 0..segments {
+            player.snake.grow();
+            if let Some(&tail) = player.snake.body.back() {
+                self.state.occupancy.add_snake(tail);
+            }
+        }
+    }
+
+    /// Award the round, then either start the intermission or finish the match
+    fn end_round(&mut self, events: &mut Vec<GameEvent>) {
+        // Last snake standing, or the top scorer of an arena round
+        let round_winner = match self.settings.mode {
+            GameMode::Classic => self.state.players.values_mut().find(|p| p.snake.alive),
+            GameMode::Arena => self.state.players.values_mut()
+                .filter(|p| p.snake.score > 0)
+                .max_by_key(|p| (p.snake.score, p.kills)),
+        };
+        let winner = round_winner.map(|p| {
+            p.round_wins += 1;
+            p.name.clone()
+        });
+
+        for player in self.state.players.values_mut() {
+            player.total_score += player.snake.score;
+        }
+
+        // Best-of-N: stop early once someone holds a majority of the rounds
+        let wins_needed = self.settings.rounds / 2 + 1;
+        let clinched = self.state.players.values().any(|p| p.round_wins >= wins_needed);
+        let standings = self.state.standings();
+
+        if self.state.round >= self.settings.rounds || clinched {
+            self.state.phase = GamePhase::MatchOver;
+            // A match in which every round was a draw has no winner
+            self.state.winner = match standings.first() {
+                Some(top) if top.round_wins > 0 || (self.settings.mode == GameMode::Arena && top.total_score > 0) => {
+                    Some(top.name.clone())
+                }
+                _ => None,
+            };
+            events.push(GameEvent::MatchOver { winner: self.state.winner.clone(), standings });
+            return;
+        }
+
+        self.state.phase = GamePhase::RoundOver;
+        self.state.winner = winner.clone();
+        self.state.countdown = INTERMISSION_DURATION;
+        self.state.countdown_ticks = 0;
+        events.push(GameEvent::RoundOver { round: self.state.round, winner, standings });
+    }
+
+    /// Reset the board for the next round and begin its countdown
+    fn start_round(&mut self, events: &mut Vec<GameEvent>) {
+        // Reset snakes to spawn positions
+        let slots: Vec<(String, usize)> = self.state.players.values()
+            .map(|p| (p.id.clone(), p.slot))
+            .collect();
+        self.state.food.clear();
+        self.state.power_ups.clear();
+
+        // Clear the board, then place snakes one by one: classic seats use their fixed
+        // spawn when there is one, everyone else goes somewhere open
+        for player in self.state.players.values_mut() {
+            player.snake.body.clear();
+        }
+        self.state.rebuild_occupancy();
+        for (id, slot) in slots {
+            let fixed = match self.settings.mode {
+                GameMode::Classic => self.state.fixed_spawn(slot),
+                GameMode::Arena => None,
+            };
+            let (pos, dir) = fixed.unwrap_or_else(|| self.find_spawn(slot));
+            if let Some(player) = self.state.players.get_mut(&id) {
+                player.snake = Snake::new(pos, dir, player_color(slot));
+                player.snake.body.iter().for_each(|&p| self.state.occupancy.add_snake(p));
+            }
+        }
+        self.state.ends_at_tick = match self.settings.mode {
+            GameMode::Classic => None,
+            GameMode::Arena => {
+                let round_ticks = self.settings.speed.ticks_for(ARENA_ROUND_DURATION) as u64;
+                Some(self.state.tick + round_ticks)
+            }
+        };
+
+        self.top_up_food(events);
+        self.powerup_spawn_ticks = 0;
+
+        // Start countdown instead of playing immediately
+        self.state.phase = GamePhase::Countdown;
+        self.state.countdown = COUNTDOWN_DURATION;
+        self.state.countdown_ticks = 0;
+        self.state.winner = None;
+        events.push(GameEvent::RoundStarted { round: self.state.round });
+    }
+
+    /// Random playable cell not covered by a snake, food or power-up;
+    /// None only when the board is completely full
+    fn random_free_cell(&mut self) -> Option<Point> {
+        self.state.occupancy.random_free(&mut self.rng)
+    }
+
+    fn spawn_food(&mut self, events: &mut Vec<GameEvent>) {
+        // Maps can steer food into zones; it goes anywhere free once those fill up
+        if let Some(point) = self.state.occupancy.random_food_cell(&mut self.rng) {
+            self.state.food.push(point);
+            self.state.occupancy.set_item(point, true);
+            events.push(GameEvent::FoodSpawned { at: point });
+        }
+    }
+
+    fn spawn_power_up(&mut self, events: &mut Vec<GameEvent>) {
+        let power_types = [PowerUpType::SpeedBoost, PowerUpType::Shield, PowerUpType::Grow, PowerUpType::Ghost];
+        let power_type = power_types[self.rng.gen_range(0..power_types.len())].clone();
+
+        if let Some(position) = self.random_free_cell() {
+            self.state.occupancy.set_item(position, true);
+            self.next_power_up_id += 1;
+            let power_up = PowerUp {
+                id: format!("pu-{}", self.next_power_up_id),
+                position,
+                power_type,
+            };
+            self.state.power_ups.push(power_up.clone());
+            events.push(GameEvent::PowerUpSpawned { power_up });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::snake::{BotBrains, BotRegistry, Player};
+
+    fn engine_with_bots(seed: u64, count: usize) -> Engine {
+        bots_in(Engine::new(seed, RoomSettings { rounds: 3, ..RoomSettings::default() }), count)
+    }
+
+    fn bots_in(mut engine: Engine, count: usize) -> Engine {
+        for slot in 0..count {
+            let (pos, dir) = engine.state.get_spawn_position(slot);
+            engine.state.add_player(Player::new_bot(format!("bot-{}", slot), format!("Bot {}", slot), "Hard".to_string(), slot, pos, dir));
+        }
+        engine
+    }
+
+    /// Drive a whole match with seeded bots and fingerprint every state along the way
+    fn run_match(seed: u64) -> (Vec<String>, Vec<GameEvent>) {
+        let mut engine = engine_with_bots(seed, 4);
+        let mut brains = BotBrains::new(Arc::new(BotRegistry::builtin()));
+        let mut bot_rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut frames = Vec::new();
+        let mut events = engine.start_match(seed);
+
+        for _ in 0..5_000 {
+            if engine.state.phase == GamePhase::MatchOver {
+                break;
+            }
+            let inputs = brains.decide_all(&engine.state, &mut bot_rng);
+            events.extend(engine.step(&inputs));
+            frames.push(serde_json::to_string(&engine.state.view()).unwrap());
+        }
+        (frames, events)
+    }
+
+    #[test]
+    fn speed_boost_moves_twice_and_eats_food_it_passes_over() {
+        let mut engine = engine_with_bots(1, 2);
+        engine.settings.power_ups_enabled = false;
+        engine.start_match(1);
+        engine.state.phase = GamePhase::Playing;
+
+        let boosted = engine.state.players.get_mut("bot-0").unwrap();
+        boosted.active_power = Some(ActivePowerUp { power_type: PowerUpType::SpeedBoost, ticks_remaining: 10 });
+        let start = *boosted.snake.head();
+        let passed = Point { x: start.x + 1, y: start.y };
+        engine.state.food = vec![passed];
+        engine.state.rebuild_occupancy();
+
+        let events = engine.step(&[]);
+
+        let boosted = &engine.state.players["bot-0"];
+        assert_eq!(*boosted.snake.head(), Point { x: start.x + 2, y: start.y });
+        assert_eq!(boosted.snake.score, 10);
+        assert!(events.contains(&GameEvent::FoodEaten { player_id: "bot-0".to_string(), at: passed }));
+        let normal = &engine.state.players["bot-1"];
+        let (spawn, _) = engine.state.get_spawn_position(1);
+        assert_eq!(*normal.snake.head(), Point { x: spawn.x - 1, y: spawn.y });
+    }
+
+    /// Two snakes one cell apart, heading into each other; "bot-1" is longer
+    fn head_on_engine(rule: HeadOnRule) -> Engine {
+        let mut engine = engine_with_bots(1, 2);
+        engine.settings.power_ups_enabled = false;
+        engine.settings.head_on = rule;
+        engine.start_match(1);
+        engine.state.phase = GamePhase::Playing;
+        engine.state.food.clear();
+
+        let row = |xs: &[i32]| xs.iter().map(|&x| Point { x, y: 10 }).collect();
+        let a = &mut engine.state.players.get_mut("bot-0").unwrap().snake;
+        a.body = row(&[10, 9, 8]);
+        (a.direction, a.next_direction) = (Direction::Right, Direction::Right);
+        let b = &mut engine.state.players.get_mut("bot-1").unwrap().snake;
+        b.body = row(&[12, 13, 14, 15]);
+        (b.direction, b.next_direction) = (Direction::Left, Direction::Left);
+        engine.state.rebuild_occupancy();
+        engine
+    }
+
+    #[test]
+    fn head_on_collisions_follow_the_room_rule_and_credit_the_killer() {
+        let mut engine = head_on_engine(HeadOnRule::BothDie);
+        engine.step(&[]);
+        let (a, b) = (&engine.state.players["bot-0"], &engine.state.players["bot-1"]);
+        assert!(!a.snake.alive && !b.snake.alive);
+        assert_eq!(a.snake.death.as_ref().unwrap().cause, DeathCause::HeadOn);
+        assert_eq!(a.snake.death.as_ref().unwrap().killer.as_deref(), Some("bot-1"));
+        assert_eq!(b.snake.death.as_ref().unwrap().killer.as_deref(), Some("bot-0"));
+        assert_eq!((a.kills, b.kills), (1, 1));
+
+        let mut engine = head_on_engine(HeadOnRule::LongerWins);
+        let events = engine.step(&[]);
+        let (a, b) = (&engine.state.players["bot-0"], &engine.state.players["bot-1"]);
+        assert!(!a.snake.alive && b.snake.alive);
+        assert_eq!((a.deaths, b.kills), (1, 1));
+        assert!(events.contains(&GameEvent::PlayerDied {
+            player_id: "bot-0".to_string(),
+            cause: DeathCause::HeadOn,
+            killer: Some("bot-1".to_string()),
+        }));
+    }
+
+    #[test]
+    fn food_finds_the_last_free_cell() {
+        let mut engine = engine_with_bots(3, 2);
+        engine.start_match(3);
+
+        let target = Point { x: 1, y: 1 };
+        let (w, h) = (engine.state.grid_width, engine.state.grid_height);
+        let mut food: Vec<Point> = (1..h - 1)
+            .flat_map(|y| (1..w - 1).map(move |x| Point { x, y }))
+            .filter(|p| *p != target && !engine.state.is_blocked(*p))
+            .collect();
+        food.sort_by_key(|p| (p.y, p.x));
+        engine.state.food = food;
+        engine.state.rebuild_occupancy();
+        assert_eq!(engine.state.occupancy.free_cells(), 1);
+
+        let mut events = Vec::new();
+        engine.spawn_food(&mut events);
+        assert_eq!(engine.state.food.last(), Some(&target));
+        assert_eq!(events, vec![GameEvent::FoodSpawned { at: target }]);
+        assert_eq!(engine.state.occupancy.free_cells(), 0);
+
+        let before = engine.state.food.len();
+        engine.spawn_food(&mut events);
+        assert_eq!(engine.state.food.len(), before);
+    }
+
+    #[test]
+    fn arena_spawns_apart_respawns_and_ranks_by_score() {
+        let settings = RoomSettings { mode: GameMode::Arena, max_players: 40, ..RoomSettings::default() };
+        let mut engine = bots_in(Engine::new(5, settings), 40);
+        engine.start_match(5);
+
+        let mut cells = std::collections::HashSet::new();
+        for player in engine.state.players.values() {
+            assert!(player.snake.body.iter().all(|p| cells.insert(*p)), "{} spawned on another snake", player.id);
+        }
+        assert_eq!(engine.state.food.len(), 40 * ARENA_FOOD_PER_PLAYER);
+        assert_eq!(engine.state.players["bot-39"].snake.color, player_color(39));
+
+        let mut brains = BotBrains::new(Arc::new(BotRegistry::builtin()));
+        let mut bot_rng = ChaCha8Rng::seed_from_u64(5);
+        let mut respawns = 0;
+        let mut over = None;
+        for _ in 0..5_000 {
+            let inputs = brains.decide_all(&engine.state, &mut bot_rng);
+            for event in engine.step(&inputs) {
+                match event {
+                    GameEvent::PlayerRespawned { .. } => respawns += 1,
+                    GameEvent::MatchOver { standings, .. } => over = Some(standings),
+                    _ => {}
+                }
+            }
+            if over.is_some() {
+                break;
+            }
+        }
+
+        let standings = over.expect("arena round should end on the clock");
+        assert!(respawns > 0);
+        assert!(standings.windows(2).all(|w| w[0].total_score >= w[1].total_score));
+        assert_eq!(engine.state.winner.as_deref(), Some(standings[0].name.as_str()));
+    }
+
+    #[test]
+    fn wrapped_boards_carry_everyone_across_and_bots_aim_across_edges() {
+        let settings = RoomSettings { wrap_edges: true, power_ups_enabled: false, ..RoomSettings::default() };
+        let mut engine = bots_in(Engine::new(3, settings), 2);
+        engine.start_match(3);
+        engine.state.phase = GamePhase::Playing;
+        let (w, h) = (engine.state.grid_width, engine.state.grid_height);
+
+        // The border ring is ordinary floor, and the nearest way to the food is over the edge
+        let snake = &mut engine.state.players.get_mut("bot-0").unwrap().snake;
+        snake.body = (0..3).map(|i| Point { x: w - 2 - i, y: 3 }).collect();
+        (snake.direction, snake.next_direction) = (Direction::Right, Direction::Right);
+        engine.state.food = vec![Point { x: 2, y: 3 }];
+        engine.state.rebuild_occupancy();
+        assert!(!engine.state.is_blocked(Point { x: 0, y: h - 1 }));
+        assert_eq!(engine.state.distance(&Point { x: w - 2, y: 3 }, &Point { x: 2, y: 3 }), 4);
+
+        let mut brains = BotBrains::new(Arc::new(BotRegistry::builtin()));
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..3 {
+            let inputs = brains.decide_all(&engine.state, &mut rng);
+            assert_eq!(inputs.iter().find(|input| input.player_id == "bot-0").map(|input| input.direction), Some(Direction::Right));
+            engine.step(&inputs);
+        }
+        let bot = &engine.state.players["bot-0"];
+        assert!(bot.snake.alive);
+        assert_eq!(*bot.snake.head(), Point { x: 1, y: 3 });
+        assert!(engine.state.occupancy.snakes_at(Point { x: w - 1, y: 3 }) > 0);
+    }
+
+    #[test]
+    fn shields_block_snakes_but_not_the_edge() {
+        let shield = || Some(ActivePowerUp { power_type: PowerUpType::Shield, ticks_remaining: 10 });
+        let mut engine = head_on_engine(HeadOnRule::BothDie);
+        engine.state.players.get_mut("bot-0").unwrap().active_power = shield();
+        engine.step(&[]);
+        assert!(engine.state.players["bot-0"].snake.alive);
+        assert!(!engine.state.players["bot-1"].snake.alive);
+
+        let mut engine = head_on_engine(HeadOnRule::BothDie);
+        let width = engine.state.grid_width;
+        let shielded = engine.state.players.get_mut("bot-0").unwrap();
+        shielded.active_power = shield();
+        shielded.snake.body = (0..3).map(|i| Point { x: width - 1 - i, y: 20 }).collect();
+        engine.state.rebuild_occupancy();
+        engine.step(&[]);
+        let shielded = &engine.state.players["bot-0"];
+        assert_eq!(shielded.snake.death.as_ref().map(|d| d.cause), Some(DeathCause::Wall));
+    }
+
+    #[test]
+    fn portals_carry_snakes_across_and_map_walls_kill() {
+        let layout = [
+            "############",
+            "#..........#",
+            "#..>.1.....#",
+            "#..........#",
+            "#..........#",
+            "#..........#",
+            "#..>.#.....#",
+            "#..........#",
+            "#....1.....#",
+            "#..........#",
+            "############",
+        ];
+        let map = GameMap::parse("test", &layout.join("\n")).unwrap();
+        let mut engine = Engine::new(2, RoomSettings { power_ups_enabled: false, ..RoomSettings::default() });
+        engine.set_map(Some(Arc::new(map)));
+        let mut engine = bots_in(engine, 2);
+        engine.start_match(2);
+        engine.state.phase = GamePhase::Playing;
+
+        let portal = Point { x: 5, y: 2 };
+        assert_eq!(engine.state.next_position(&Point { x: 4, y: 2 }, Direction::Right), Point { x: 5, y: 8 });
+        assert!(engine.state.is_blocked(Point { x: 5, y: 6 }));
+        assert!(engine.state.food.iter().all(|f| !engine.state.is_blocked(*f) && *f != portal));
+
+        engine.step(&[]);
+        engine.step(&[]);
+        let traveller = &engine.state.players["bot-0"];
+        assert_eq!(*traveller.snake.head(), Point { x: 5, y: 8 });
+        assert!(traveller.snake.alive);
+        let crashed = &engine.state.players["bot-1"];
+        assert_eq!(crashed.snake.death.as_ref().map(|d| d.cause), Some(DeathCause::Wall));
+    }
+
+    #[test]
+    fn identical_seeds_and_inputs_replay_byte_for_byte() {
+        let (frames_a, events_a) = run_match(42);
+        let (frames_b, events_b) = run_match(42);
+
+        assert!(events_a.iter().any(|e| matches!(e, GameEvent::MatchOver { .. })));
+        assert_eq!(frames_a, frames_b);
+        assert_eq!(events_a, events_b);
+    }
+}
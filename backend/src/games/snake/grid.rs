@@ -0,0 +1,210 @@
+use rand::Rng;
+
+use super::Point;
+
+/// Prefix counts over a 0/1 array, for O(log n) updates and "k-th set cell" lookups
+#[derive(Clone, Debug, Default)]
+struct Fenwick {
+    tree: Vec<u32>, // 1-based
+}
+
+impl Fenwick {
+    fn new(len: usize) -> Self {
+        Fenwick { tree: vec![0; len + 1] }
+    }
+
+    fn add(&mut self, idx: usize, set: bool) {
+        let mut i = idx + 1;
+        while i < self.tree.len() {
+            if set {
+                self.tree[i] += 1;
+            } else {
+                self.tree[i] -= 1;
+            }
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    fn total(&self) -> usize {
+        let mut i = self.tree.len() - 1;
+        let mut total = 0;
+        while i > 0 {
+            total += self.tree[i] as usize;
+            i -= i & i.wrapping_neg();
+        }
+        total
+    }
+
+    /// Index of the set cell with `k` set cells before it
+    fn select(&self, k: usize) -> usize {
+        let mut remaining = k as u32;
+        let mut pos = 0;
+        let mut step = (self.tree.len() - 1).next_power_of_two();
+        while step > 0 {
+            let next = pos + step;
+            if next < self.tree.len() && self.tree[next] <= remaining {
+                remaining -= self.tree[next];
+                pos = next;
+            }
+            step /= 2;
+        }
+        pos
+    }
+}
+
+/// Static contents of a cell, from the map
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Terrain {
+    #[default]
+    Open,
+    Wall,
+    Portal,
+}
+
+/// Per-cell snake occupancy plus a count of empty playable cells.
+/// Kept in step with the board by the engine so lookups are O(1) and
+/// picking a random empty cell is O(log n) and never has to guess.
+#[derive(Clone, Debug, Default)]
+pub struct Occupancy {
+    width: i32,
+    height: i32,
+    wrap: bool,              // Toroidal board: the outer ring is playable too
+    terrain: Vec<Terrain>,
+    snakes: Vec<u16>,        // Snake segments on each cell (a growing tail can stack)
+    items: Vec<bool>,        // Food or power-up on each cell
+    food_zone: Vec<bool>,    // Cells food prefers, if the map has any
+    free: Vec<bool>,         // Open playable cell with nothing on it
+    free_tree: Fenwick,      // Over `free`
+    zone_free_tree: Fenwick, // Over `free` cells inside a food zone
+}
+
+impl Occupancy {
+    pub fn new(width: i32, height: i32, wrap: bool) -> Self {
+        let cells = (width.max(0) * height.max(0)) as usize;
+        let mut grid = Occupancy {
+            width,
+            height,
+            wrap,
+            terrain: vec![Terrain::Open; cells],
+            snakes: vec![0; cells],
+            items: vec![false; cells],
+            food_zone: vec![false; cells],
+            free: vec![false; cells],
+            free_tree: Fenwick::new(cells),
+            zone_free_tree: Fenwick::new(cells),
+        };
+        for idx in 0..cells {
+            grid.sync(idx);
+        }
+        grid
+    }
+
+    fn index(&self, p: Point) -> Option<usize> {
+        let inside = p.x >= 0 && p.x < self.width && p.y >= 0 && p.y < self.height;
+        inside.then(|| (p.y * self.width + p.x) as usize)
+    }
+
+    fn point(&self, idx: usize) -> Point {
+        Point { x: idx as i32 % self.width, y: idx as i32 / self.width }
+    }
+
+    /// Inside the border, or anywhere at all when the edges wrap
+    fn is_playable(&self, idx: usize) -> bool {
+        let p = self.point(idx);
+        self.wrap || (p.x > 0 && p.x < self.width - 1 && p.y > 0 && p.y < self.height - 1)
+    }
+
+    /// Update the free flag (and the trees) to match the cell's contents
+    fn sync(&mut self, idx: usize) {
+        let free = self.terrain[idx] == Terrain::Open
+            && self.snakes[idx] == 0
+            && !self.items[idx]
+            && self.is_playable(idx);
+        if free == self.free[idx] {
+            return;
+        }
+        self.free[idx] = free;
+        self.free_tree.add(idx, free);
+        if self.food_zone[idx] {
+            self.zone_free_tree.add(idx, free);
+        }
+    }
+
+    pub fn set_terrain(&mut self, p: Point, terrain: Terrain) {
+        if let Some(idx) = self.index(p) {
+            self.terrain[idx] = terrain;
+            self.sync(idx);
+        }
+    }
+
+    pub fn add_food_zone(&mut self, p: Point) {
+        let Some(idx) = self.index(p) else {
+            return;
+        };
+        if !self.food_zone[idx] {
+            self.food_zone[idx] = true;
+            if self.free[idx] {
+                self.zone_free_tree.add(idx, true);
+            }
+        }
+    }
+
+    pub fn add_snake(&mut self, p: Point) {
+        if let Some(idx) = self.index(p) {
+            self.snakes[idx] += 1;
+            self.sync(idx);
+        }
+    }
+
+    pub fn remove_snake(&mut self, p: Point) {
+        if let Some(idx) = self.index(p) {
+            self.snakes[idx] = self.snakes[idx].saturating_sub(1);
+            self.sync(idx);
+        }
+    }
+
+    pub fn set_item(&mut self, p: Point, present: bool) {
+        if let Some(idx) = self.index(p) {
+            self.items[idx] = present;
+            self.sync(idx);
+        }
+    }
+
+    pub fn terrain_at(&self, p: Point) -> Terrain {
+        self.index(p).map_or(Terrain::Open, |idx| self.terrain[idx])
+    }
+
+    pub fn is_wall(&self, p: Point) -> bool {
+        self.terrain_at(p) == Terrain::Wall
+    }
+
+    /// Open playable cell with no snake, food or power-up on it
+    pub fn is_free(&self, p: Point) -> bool {
+        self.index(p).is_some_and(|idx| self.free[idx])
+    }
+
+    /// Snake segments on `p`; 0 outside the grid
+    pub fn snakes_at(&self, p: Point) -> u16 {
+        self.index(p).map_or(0, |idx| self.snakes[idx])
+    }
+
+    pub fn free_cells(&self) -> usize {
+        self.free_tree.total()
+    }
+
+    /// Uniformly random empty playable cell; None only when the board is full.
+    /// Depends only on what is on the board, not on the order it got there.
+    pub fn random_free(&self, rng: &mut impl Rng) -> Option<Point> {
+        let total = self.free_cells();
+        (total > 0).then(|| self.point(self.free_tree.select(rng.gen_range(0..total))))
+    }
+
+    /// Like `random_free`, but inside a food zone while any zone cell is empty
+    pub fn random_food_cell(&self, rng: &mut impl Rng) -> Option<Point> {
+        let in_zones = self.zone_free_tree.total();
+        if in_zones > 0 {
+            return Some(self.point(self.zone_free_tree.select(rng.gen_range(0..in_zones))));
+        }
+        self.random_free(rng)
+    }
+}
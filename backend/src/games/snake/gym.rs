@@ -0,0 +1,922 @@
+use actix::prelude::*;
+use actix_web_actors::ws;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::bots::BotStrategy;
+use super::grid::Terrain;
+use super::{
+    BotBrains, BotRegistry, Direction, Engine, GameEvent, GameMode, GamePhase, GameState,
+    Input, MapCatalog, Player, Point, RoomSettings, CLIENT_TIMEOUT, HEARTBEAT_INTERVAL,
+};
+
+/// Observation layers, in the order they're stacked
+pub const CHANNELS: [&str; 8] = [
+    "blocked",    // Walls, and anything past the edge of the board
+    "head",       // The agent's own head
+    "body",       // The agent's own segments, head included
+    "enemy_head",
+    "enemy_body",
+    "food",
+    "power_up",
+    "portal",
+];
+
+/// What action indices mean
+pub const ACTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+pub const MAX_VIEW_RADIUS: i32 = 20;
+const MAX_POLICY_NAME_LEN: usize = 40;
+const MAX_OPEN_ENVS: usize = 64;
+const MAX_OPEN_ENVS_PER_USER: usize = 4; // So one account can't take every slot
+const ENV_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60); // Untouched HTTP envs are dropped after this
+const POLICY_PREFIX: &str = "policy:";
+
+// =============================================================================
+// OBSERVATIONS
+// =============================================================================
+
+/// The board as stacked 0/1 grids, channel-major: `data[(c * height + row) * width + col]`
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Observation {
+    pub channels: usize,
+    pub height: usize,
+    pub width: usize,
+    pub data: Vec<u8>,
+}
+
+impl Observation {
+    fn set(&mut self, channel: usize, row: usize, col: usize) {
+        self.data[(channel * self.height + row) * self.width + col] = 1;
+    }
+
+    pub fn get(&self, channel: usize, row: usize, col: usize) -> bool {
+        self.data[(channel * self.height + row) * self.width + col] != 0
+    }
+}
+
+/// The board as `player_id` sees it: all of it, or with `view_radius` a square of
+/// side `2r + 1` centred on its head, so the snake is always in the middle
+pub fn observe(state: &GameState, player_id: &str, view_radius: Option<i32>) -> Observation {
+    let snake = state.players.get(player_id).map(|player| &player.snake);
+    let own: HashSet<Point> = snake.map(|snake| snake.body.iter().copied().collect()).unwrap_or_default();
+
+    // Board cell -> observation cell
+    let centre = snake.map_or(Point { x: state.grid_width / 2, y: state.grid_height / 2 }, |s| *s.head());
+    let (height, width) = match view_radius {
+        Some(r) => ((2 * r + 1) as usize, (2 * r + 1) as usize),
+        None => (state.grid_height as usize, state.grid_width as usize),
+    };
+    let cell = |p: &Point| -> Option<(usize, usize)> {
+        let (row, col) = match view_radius {
+            Some(r) => {
+                let (dx, dy) = state.offset_between(&centre, p);
+                (dy + r, dx + r)
+            }
+            None => (p.y, p.x),
+        };
+        let inside = row >= 0 && col >= 0 && (row as usize) < height && (col as usize) < width;
+        inside.then_some((row as usize, col as usize))
+    };
+
+    let mut obs = Observation { channels: CHANNELS.len(), height, width, data: vec![0; CHANNELS.len() * height * width] };
+    for row in 0..height {
+        for col in 0..width {
+            let p = match view_radius {
+                Some(r) => state.wrap(Point { x: centre.x + col as i32 - r, y: centre.y + row as i32 - r }),
+                None => Point { x: col as i32, y: row as i32 },
+            };
+            let on_board = p.x >= 0 && p.x < state.grid_width && p.y >= 0 && p.y < state.grid_height;
+            if !on_board || state.occupancy.is_wall(p) {
+                obs.set(0, row, col);
+            }
+            if !on_board {
+                continue;
+            }
+            if state.occupancy.terrain_at(p) == Terrain::Portal {
+                obs.set(7, row, col);
+            }
+            // Segments this agent doesn't own belong to someone else (a grown tail can stack)
+            let own_here = own.contains(&p) as u16;
+            if own_here > 0 {
+                obs.set(2, row, col);
+            }
+            if state.occupancy.snakes_at(p) > own_here {
+                obs.set(4, row, col);
+            }
+        }
+    }
+
+    for player in state.players.values().filter(|player| player.snake.alive) {
+        if let Some((row, col)) = cell(player.snake.head()) {
+            obs.set(if player.id == player_id { 1 } else { 3 }, row, col);
+        }
+    }
+    for (row, col) in state.food.iter().filter_map(&cell) {
+        obs.set(5, row, col);
+    }
+    for (row, col) in state.power_ups.iter().filter_map(|power_up| cell(&power_up.position)) {
+        obs.set(6, row, col);
+    }
+    obs
+}
+
+// =============================================================================
+// ENVIRONMENT
+// =============================================================================
+
+/// Reward for each thing that can happen to an agent in a step
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RewardConfig {
+    pub food: f32,
+    pub death: f32,
+    pub kill: f32,     // Another snake died running into this one
+    pub win: f32,      // Per round won
+    pub survival: f32, // Every step the agent is still alive at the end of
+    pub approach: f32, // Per cell the agent moved closer to the nearest food (negative if it moved away)
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        RewardConfig { food: 1.0, death: -1.0, kill: 0.5, win: 1.0, survival: 0.0, approach: 0.0 }
+    }
+}
+
+/// Everything `SnakeEnv::reset` can vary besides the seed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvConfig {
+    pub settings: RoomSettings,   // `max_players` follows the seats
+    pub agents: usize,            // Seats steered through `step`
+    pub opponents: Vec<String>,   // Bot strategy for each of the other seats
+    pub view_radius: Option<i32>, // Egocentric crop; None observes the whole board
+    pub reward: RewardConfig,
+    pub max_steps: u64,           // Episodes still running after this many steps are cut off
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            settings: RoomSettings { power_ups_enabled: false, ..RoomSettings::default() },
+            agents: 1,
+            opponents: vec!["Medium".to_string()],
+            view_radius: None,
+            reward: RewardConfig::default(),
+            max_steps: 2_000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnvError {
+    NotReset,
+    EpisodeOver, // `step` after `done`; reset first
+    BadConfig(String),
+    BadActions(String),
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::NotReset => write!(f, "Call reset before step"),
+            EnvError::EpisodeOver => write!(f, "The episode is over; call reset"),
+            EnvError::BadConfig(msg) => write!(f, "Bad config: {}", msg),
+            EnvError::BadActions(msg) => write!(f, "Bad actions: {}", msg),
+        }
+    }
+}
+
+/// What `reset` and `step` return, one entry per agent in each list
+#[derive(Clone, Debug, Serialize)]
+pub struct StepResult {
+    pub observations: Vec<Observation>,
+    pub rewards: Vec<f32>,
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StepInfo {
+    pub agent_ids: Vec<String>,
+    pub steps: u64,
+    pub tick: u64,
+    pub round: u8,
+    pub alive: Vec<bool>,
+    pub lengths: Vec<usize>,
+    pub scores: Vec<u32>,
+    pub truncated: bool,         // Hit `max_steps` rather than finishing the match
+    pub winner: Option<String>,  // Match winner's name once it's over
+    pub events: Vec<GameEvent>,  // Everything that happened during the step
+}
+
+/// Per-agent facts compared across a step to work out its reward
+struct Snapshot {
+    alive: bool,
+    round: u8,
+    round_wins: u8,
+    food_distance: Option<i32>,
+}
+
+impl Snapshot {
+    fn of(state: &GameState, id: &str) -> Self {
+        let player = state.players.get(id);
+        let food_distance = player.and_then(|player| {
+            state.food.iter().map(|food| state.distance(player.snake.head(), food)).min()
+        });
+        Snapshot {
+            alive: player.is_some_and(|player| player.snake.alive),
+            round: state.round,
+            round_wins: player.map_or(0, |player| player.round_wins),
+            food_distance,
+        }
+    }
+}
+
+/// Gym-style access to the engine: `reset` starts an episode and `step` advances
+/// it one tick with a move for each agent. Opponents are ordinary bots, and the
+/// countdown and intermissions are skipped so every observation is of live play.
+pub struct SnakeEnv {
+    registry: Arc<BotRegistry>,
+    maps: Arc<MapCatalog>,
+    config: EnvConfig,
+    engine: Option<Engine>,
+    brains: BotBrains,
+    rng: ChaCha8Rng, // Opponents' dice
+    agents: Vec<String>,
+    steps: u64,
+    done: bool,
+}
+
+impl SnakeEnv {
+    pub fn new(registry: Arc<BotRegistry>, maps: Arc<MapCatalog>) -> Self {
+        SnakeEnv {
+            brains: BotBrains::new(registry.clone()),
+            registry,
+            maps,
+            config: EnvConfig::default(),
+            engine: None,
+            rng: ChaCha8Rng::seed_from_u64(0),
+            agents: Vec::new(),
+            steps: 0,
+            done: false,
+        }
+    }
+
+    pub fn state(&self) -> Option<&GameState> {
+        self.engine.as_ref().map(|engine| &engine.state)
+    }
+
+    /// Start a new episode; the same seed and config always play out the same way
+    /// against bots that don't look at the clock
+    pub fn reset(&mut self, seed: u64, config: EnvConfig) -> Result<StepResult, EnvError> {
+        let bad = |msg: String| Err(EnvError::BadConfig(msg));
+        let seats = config.agents + config.opponents.len();
+        if config.agents == 0 {
+            return bad("at least one agent is needed".to_string());
+        }
+        if seats > config.settings.mode.max_players() {
+            return bad(format!("{:?} mode seats at most {} snakes", config.settings.mode, config.settings.mode.max_players()));
+        }
+        if config.view_radius.is_some_and(|r| !(1..=MAX_VIEW_RADIUS).contains(&r)) {
+            return bad(format!("view_radius must be between 1 and {}", MAX_VIEW_RADIUS));
+        }
+        if config.max_steps == 0 {
+            return bad("max_steps must be at least 1".to_string());
+        }
+        let opponents = config.opponents.iter()
+            .map(|name| self.registry.resolve(name).map(str::to_string).ok_or_else(|| name.clone()))
+            .collect::<Result<Vec<_>, _>>();
+        let opponents = match opponents {
+            Ok(opponents) => opponents,
+            Err(name) => return bad(format!("no bot strategy named '{}'", name)),
+        };
+        let map = match config.settings.map_id.as_deref().map(|id| self.maps.get(id)) {
+            None => None,
+            Some(Ok(Some(map))) => Some(map),
+            Some(Ok(None)) => return bad("map not found".to_string()),
+            Some(Err(e)) => return bad(format!("failed to load map: {}", e)),
+        };
+
+        let settings = RoomSettings { max_players: seats, bot_arena: false, ..config.settings.clone() }.normalized();
+        let mut engine = Engine::new(seed, settings);
+        engine.set_map(map);
+        self.agents = (0..config.agents).map(|i| format!("agent-{}", i)).collect();
+        let seated = self.agents.iter()
+            .map(|id| (id.clone(), None))
+            .chain(opponents.into_iter().enumerate().map(|(i, strategy)| (format!("bot-{}", i), Some(strategy))));
+        for (slot, (id, strategy)) in seated.enumerate() {
+            let (pos, dir) = engine.state.get_spawn_position(slot);
+            engine.add_player(match strategy {
+                Some(strategy) => Player::new_bot(id, format!("{} #{}", strategy, slot + 1), strategy, slot, pos, dir),
+                None => Player { ready: true, ..Player::new(id, format!("Agent #{}", slot + 1), slot, pos, dir) },
+            });
+        }
+
+        self.brains = BotBrains::new(self.registry.clone());
+        self.brains.warm_up(&engine.state);
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut events = engine.start_match(seed);
+        skip_to_play(&mut engine, &mut events);
+        self.engine = Some(engine);
+        self.config = config;
+        self.steps = 0;
+        self.done = false;
+        Ok(self.result(vec![0.0; self.agents.len()], events, false))
+    }
+
+    /// Advance one tick. `actions[i]` steers agent `i`; `None` keeps it going straight.
+    pub fn step(&mut self, actions: &[Option<Direction>]) -> Result<StepResult, EnvError> {
+        let Some(engine) = self.engine.as_mut() else {
+            return Err(EnvError::NotReset);
+        };
+        if self.done {
+            return Err(EnvError::EpisodeOver);
+        }
+        if actions.len() != self.agents.len() {
+            return Err(EnvError::BadActions(format!("expected {} actions, got {}", self.agents.len(), actions.len())));
+        }
+
+        let before: Vec<Snapshot> = self.agents.iter().map(|id| Snapshot::of(&engine.state, id)).collect();
+        let mut inputs: Vec<Input> = self.agents.iter().zip(actions)
+            .filter_map(|(id, action)| action.map(|direction| Input { player_id: id.clone(), direction }))
+            .collect();
+        inputs.extend(self.brains.decide_all(&engine.state, &mut self.rng));
+        let mut events = engine.step(&inputs);
+        skip_to_play(engine, &mut events);
+        self.steps += 1;
+
+        let state = &engine.state;
+        let reward = &self.config.reward;
+        let rewards = self.agents.iter().zip(&before)
+            .map(|(id, before)| {
+                let after = Snapshot::of(state, id);
+                let mut total = 0.0;
+                for event in &events {
+                    match event {
+                        GameEvent::FoodEaten { player_id, .. } if player_id == id => total += reward.food,
+                        GameEvent::PlayerDied { player_id, .. } if player_id == id => total += reward.death,
+                        GameEvent::PlayerDied { killer: Some(killer), .. } if killer == id => total += reward.kill,
+                        _ => {}
+                    }
+                }
+                total += reward.win * after.round_wins.saturating_sub(before.round_wins) as f32;
+                if after.alive {
+                    total += reward.survival;
+                }
+                // Only within a round: a new round puts everything somewhere else
+                if let (true, Some(from), Some(to)) = (before.alive && after.alive && before.round == after.round, before.food_distance, after.food_distance) {
+                    total += reward.approach * (from - to) as f32;
+                }
+                total
+            })
+            .collect();
+
+        let match_over = state.phase == GamePhase::MatchOver;
+        let agents_out = state.mode == GameMode::Classic && !self.agents.iter().any(|id| {
+            state.players.get(id).is_some_and(|player| player.snake.alive)
+        });
+        let truncated = !match_over && self.steps >= self.config.max_steps;
+        self.done = match_over || agents_out || truncated;
+        Ok(self.result(rewards, events, truncated))
+    }
+
+    fn result(&self, rewards: Vec<f32>, events: Vec<GameEvent>, truncated: bool) -> StepResult {
+        let state = &self.engine.as_ref().expect("episode started").state;
+        let players: Vec<Option<&Player>> = self.agents.iter().map(|id| state.players.get(id)).collect();
+        StepResult {
+            observations: self.agents.iter().map(|id| observe(state, id, self.config.view_radius)).collect(),
+            rewards,
+            done: self.done,
+            info: StepInfo {
+                agent_ids: self.agents.clone(),
+                steps: self.steps,
+                tick: state.tick,
+                round: state.round,
+                alive: players.iter().map(|p| p.is_some_and(|p| p.snake.alive)).collect(),
+                lengths: players.iter().map(|p| p.map_or(0, |p| p.snake.body.len())).collect(),
+                scores: players.iter().map(|p| p.map_or(0, |p| p.total_score + p.snake.score)).collect(),
+                truncated,
+                winner: (state.phase == GamePhase::MatchOver).then(|| state.winner.clone()).flatten(),
+                events,
+            },
+        }
+    }
+}
+
+/// Run through countdowns and intermissions; nothing moves during them
+fn skip_to_play(engine: &mut Engine, events: &mut Vec<GameEvent>) {
+    while matches!(engine.state.phase, GamePhase::Countdown | GamePhase::RoundOver) {
+        events.extend(engine.step(&[]));
+    }
+}
+
+/// An agent's move on the wire: a direction name, its index in `ACTIONS`, or null to keep going
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Action {
+    Index(usize),
+    Named(Direction),
+}
+
+pub fn parse_actions(actions: &[Option<Action>]) -> Result<Vec<Option<Direction>>, EnvError> {
+    actions.iter()
+        .map(|action| match action {
+            None => Ok(None),
+            Some(Action::Named(direction)) => Ok(Some(*direction)),
+            Some(Action::Index(i)) => ACTIONS.get(*i).copied().map(Some)
+                .ok_or_else(|| EnvError::BadActions(format!("action {} is out of range 0-{}", i, ACTIONS.len() - 1))),
+        })
+        .collect()
+}
+
+// =============================================================================
+// OPEN ENVIRONMENTS
+// =============================================================================
+
+struct OpenEnv {
+    env: Arc<Mutex<SnakeEnv>>,
+    owner: String, // User who opened it; nobody else can use it
+    last_used: Instant,
+}
+
+/// Why `GymEnvs` couldn't open another environment
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnvLimit {
+    Full,    // Every slot is taken
+    PerUser, // This user already has their share open
+}
+
+impl fmt::Display for EnvLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvLimit::Full => write!(f, "Too many open environments, try again later"),
+            EnvLimit::PerUser => write!(f, "You already have {} environments open", MAX_OPEN_ENVS_PER_USER),
+        }
+    }
+}
+
+/// Environments created over HTTP, by id; the ones nobody has touched in a while are dropped.
+/// WebSocket sessions hold their own environment but count against the same limits.
+pub struct GymEnvs {
+    registry: Arc<BotRegistry>,
+    maps: Arc<MapCatalog>,
+    envs: Mutex<HashMap<String, OpenEnv>>,
+    sockets: Mutex<HashMap<String, usize>>, // Owner -> live `GymSession`s
+}
+
+impl GymEnvs {
+    pub fn new(registry: Arc<BotRegistry>, maps: Arc<MapCatalog>) -> Self {
+        GymEnvs { registry, maps, envs: Mutex::new(HashMap::new()), sockets: Mutex::new(HashMap::new()) }
+    }
+
+    fn env(&self) -> SnakeEnv {
+        SnakeEnv::new(self.registry.clone(), self.maps.clone())
+    }
+
+    /// Whether `owner` may open one more, given what's open now
+    fn check_limits(envs: &HashMap<String, OpenEnv>, sockets: &HashMap<String, usize>, owner: &str) -> Result<(), EnvLimit> {
+        if envs.len() + sockets.values().sum::<usize>() >= MAX_OPEN_ENVS {
+            return Err(EnvLimit::Full);
+        }
+        let own = envs.values().filter(|open| open.owner == owner).count() + sockets.get(owner).copied().unwrap_or(0);
+        if own >= MAX_OPEN_ENVS_PER_USER {
+            return Err(EnvLimit::PerUser);
+        }
+        Ok(())
+    }
+
+    /// An environment for `owner`'s WebSocket session. Its slot is given back when
+    /// the session drops it.
+    pub fn lease(self: &Arc<Self>, owner: &str) -> Result<EnvLease, EnvLimit> {
+        let mut envs = self.envs.lock().unwrap();
+        envs.retain(|_, open| open.last_used.elapsed() < ENV_IDLE_TIMEOUT);
+        let mut sockets = self.sockets.lock().unwrap();
+        Self::check_limits(&envs, &sockets, owner)?;
+        *sockets.entry(owner.to_string()).or_insert(0) += 1;
+        Ok(EnvLease { env: Arc::new(Mutex::new(self.env())), envs: self.clone(), owner: owner.to_string() })
+    }
+
+    /// Id of a new environment for `owner`
+    pub fn open(&self, owner: &str) -> Result<String, EnvLimit> {
+        let mut envs = self.envs.lock().unwrap();
+        envs.retain(|_, open| open.last_used.elapsed() < ENV_IDLE_TIMEOUT);
+        Self::check_limits(&envs, &self.sockets.lock().unwrap(), owner)?;
+        let id = Uuid::new_v4().to_string();
+        let env = Arc::new(Mutex::new(self.env()));
+        envs.insert(id.clone(), OpenEnv { env, owner: owner.to_string(), last_used: Instant::now() });
+        Ok(id)
+    }
+
+    /// `owner`'s environment `id`; anyone else's looks the same as one that doesn't exist
+    pub fn get(&self, id: &str, owner: &str) -> Option<Arc<Mutex<SnakeEnv>>> {
+        let mut envs = self.envs.lock().unwrap();
+        let open = envs.get_mut(id).filter(|open| open.owner == owner)?;
+        open.last_used = Instant::now();
+        Some(open.env.clone())
+    }
+
+    /// False if `owner` has no environment `id`
+    pub fn close(&self, id: &str, owner: &str) -> bool {
+        let mut envs = self.envs.lock().unwrap();
+        if envs.get(id).is_some_and(|open| open.owner == owner) {
+            envs.remove(id);
+            return true;
+        }
+        false
+    }
+}
+
+/// A WebSocket session's environment, holding one of the `GymEnvs` slots
+pub struct EnvLease {
+    env: Arc<Mutex<SnakeEnv>>,
+    envs: Arc<GymEnvs>,
+    owner: String,
+}
+
+impl Drop for EnvLease {
+    fn drop(&mut self) {
+        let mut sockets = self.envs.sockets.lock().unwrap();
+        if let Some(count) = sockets.get_mut(&self.owner) {
+            *count -= 1;
+            if *count == 0 {
+                sockets.remove(&self.owner);
+            }
+        }
+    }
+}
+
+// =============================================================================
+// TRAINED POLICIES
+// =============================================================================
+
+/// One fully-connected layer; `weights[out][in]`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DenseLayer {
+    pub weights: Vec<Vec<f32>>,
+    pub bias: Vec<f32>,
+}
+
+/// A trained agent: a small network from a flattened `Observation` to a score for
+/// each of `ACTIONS`, with ReLU between layers. Saved as JSON.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Policy {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub view_radius: Option<i32>, // Must match the observations it was trained on
+    pub layers: Vec<DenseLayer>,
+}
+
+impl Policy {
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_POLICY_NAME_LEN {
+            return Err(format!("Policy names must be 1 to {} characters", MAX_POLICY_NAME_LEN));
+        }
+        if self.view_radius.is_some_and(|r| !(1..=MAX_VIEW_RADIUS).contains(&r)) {
+            return Err(format!("view_radius must be between 1 and {}", MAX_VIEW_RADIUS));
+        }
+        let mut inputs = None;
+        for (i, layer) in self.layers.iter().enumerate() {
+            let width = layer.weights.first().map_or(0, Vec::len);
+            if width == 0 || inputs.is_some_and(|n| n != width) || layer.weights.iter().any(|row| row.len() != width) {
+                return Err(format!("Layer {} has the wrong number of inputs", i));
+            }
+            if layer.bias.len() != layer.weights.len() {
+                return Err(format!("Layer {} has {} outputs but {} biases", i, layer.weights.len(), layer.bias.len()));
+            }
+            if layer.weights.iter().flatten().chain(&layer.bias).any(|w| !w.is_finite()) {
+                return Err(format!("Layer {} has weights that aren't numbers", i));
+            }
+            inputs = Some(layer.weights.len());
+        }
+        match inputs {
+            Some(outputs) if outputs == ACTIONS.len() => Ok(()),
+            Some(outputs) => Err(format!("The last layer has {} outputs, not {}", outputs, ACTIONS.len())),
+            None => Err("A policy needs at least one layer".to_string()),
+        }
+    }
+
+    /// Score for each action, or None if the observation isn't the size it was trained on
+    pub fn scores(&self, obs: &Observation) -> Option<Vec<f32>> {
+        if self.layers.first()?.weights.first()?.len() != obs.data.len() {
+            return None;
+        }
+        let mut values: Vec<f32> = obs.data.iter().map(|&v| v as f32).collect();
+        for (i, layer) in self.layers.iter().enumerate() {
+            let hidden = i + 1 < self.layers.len();
+            values = layer.weights.iter().zip(&layer.bias)
+                .map(|(row, bias)| {
+                    let sum = bias + row.iter().zip(&values).map(|(w, v)| w * v).sum::<f32>();
+                    if hidden { sum.max(0.0) } else { sum }
+                })
+                .collect();
+        }
+        Some(values)
+    }
+
+    /// The bot's name in a `BotRegistry`
+    pub fn strategy(&self) -> String {
+        format!("{}{}", POLICY_PREFIX, self.name.trim())
+    }
+
+    /// Every valid `*.json` policy in `dir`; a missing directory has none
+    pub fn load_dir(dir: &Path) -> io::Result<Vec<Policy>> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut policies = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let policy = serde_json::from_slice::<Policy>(&std::fs::read(&path)?)
+                .map_err(|e| e.to_string())
+                .and_then(|policy| policy.validate().map(|()| policy));
+            match policy {
+                Ok(policy) => policies.push(policy),
+                Err(e) => log::warn!("Skipping policy {}: {}", path.display(), e),
+            }
+        }
+        policies.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(policies)
+    }
+}
+
+/// Plays a trained policy; it never turns back on itself
+pub struct PolicyBot {
+    policy: Arc<Policy>,
+}
+
+impl PolicyBot {
+    pub fn new(policy: Arc<Policy>) -> Self {
+        PolicyBot { policy }
+    }
+}
+
+impl BotStrategy for PolicyBot {
+    fn decide(&mut self, state: &GameState, player_id: &str, _rng: &mut dyn RngCore) -> Option<Direction> {
+        let current = state.players.get(player_id)?.snake.direction;
+        let scores = self.policy.scores(&observe(state, player_id, self.policy.view_radius))?;
+        ACTIONS.iter().zip(scores)
+            .filter(|(dir, _)| **dir != current.opposite())
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(dir, _)| *dir)
+    }
+}
+
+// =============================================================================
+// WEBSOCKET SESSION
+// =============================================================================
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", content = "payload")]
+pub enum GymCommand {
+    Reset {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default)]
+        config: EnvConfig,
+    },
+    Step { actions: Vec<Option<Action>> },
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", content = "payload")]
+pub enum GymReply {
+    Step(StepResult),
+    Error { message: String },
+}
+
+/// One environment per socket, for training loops that step faster than HTTP allows
+pub struct GymSession {
+    lease: EnvLease,
+    hb: Instant,
+}
+
+impl GymSession {
+    pub fn new(lease: EnvLease) -> Self {
+        GymSession { lease, hb: Instant::now() }
+    }
+
+    fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+                log::warn!("Gym client heartbeat failed, disconnecting!");
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    /// Runs the command on a blocking thread, since opponents can think for a while. The
+    /// session waits for it, so replies go out in order and commands never overlap.
+    fn handle_command(&mut self, cmd: GymCommand, ctx: &mut ws::WebsocketContext<Self>) {
+        let env = self.lease.env.clone();
+        let run = actix_web::web::block(move || {
+            let mut env = env.lock().unwrap_or_else(|e| e.into_inner());
+            match cmd {
+                GymCommand::Reset { seed, config } => env.reset(seed, config),
+                GymCommand::Step { actions } => parse_actions(&actions).and_then(|actions| env.step(&actions)),
+            }
+        });
+        ctx.wait(run.into_actor(self).map(|result, _, ctx| {
+            let reply = match result {
+                Ok(Ok(result)) => GymReply::Step(result),
+                Ok(Err(e)) => GymReply::Error { message: e.to_string() },
+                Err(_) => GymReply::Error { message: "Failed to run environment".to_string() },
+            };
+            ctx.text(serde_json::to_string(&reply).unwrap());
+        }));
+    }
+}
+
+impl Actor for GymSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.hb(ctx);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for GymSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => {
+                self.hb = Instant::now();
+            }
+            Ok(ws::Message::Text(text)) => {
+                self.hb = Instant::now();
+                match serde_json::from_str::<GymCommand>(&text) {
+                    Ok(cmd) => self.handle_command(cmd, ctx),
+                    Err(e) => {
+                        let reply = GymReply::Error { message: format!("Unreadable command: {}", e) };
+                        ctx.text(serde_json::to_string(&reply).unwrap());
+                    }
+                }
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> SnakeEnv {
+        SnakeEnv::new(Arc::new(BotRegistry::builtin()), Arc::new(MapCatalog::builtin()))
+    }
+
+    #[test]
+    fn episodes_repeat_for_a_seed_and_reward_what_happened() {
+        let config = EnvConfig { opponents: vec!["Pathfinder".to_string()], ..EnvConfig::default() };
+        let play = |seed| {
+            let mut env = env();
+            let first = env.reset(seed, config.clone()).unwrap();
+            assert_eq!(env.state().unwrap().phase, GamePhase::Playing);
+            let mut results = vec![first];
+            while !results.last().unwrap().done {
+                results.push(env.step(&[Some(ACTIONS[results.len() % 7 / 2])]).unwrap());
+            }
+            assert_eq!(env.step(&[None]).unwrap_err(), EnvError::EpisodeOver);
+            results
+        };
+
+        let (a, b) = (play(5), play(5));
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(&b) {
+            assert_eq!(a.observations, b.observations);
+            assert_eq!(a.rewards, b.rewards);
+        }
+
+        // Every reward is explained by the step's events
+        for result in &a[1..] {
+            let mut expected = 0.0;
+            for event in &result.info.events {
+                match event {
+                    GameEvent::FoodEaten { player_id, .. } if player_id == "agent-0" => expected += 1.0,
+                    GameEvent::PlayerDied { player_id, .. } if player_id == "agent-0" => expected -= 1.0,
+                    GameEvent::PlayerDied { killer: Some(killer), .. } if killer == "agent-0" => expected += 0.5,
+                    GameEvent::MatchOver { winner: Some(winner), .. } if winner == "Agent #1" => expected += 1.0,
+                    _ => {}
+                }
+            }
+            assert_eq!(result.rewards, vec![expected]);
+        }
+    }
+
+    #[test]
+    fn the_outermost_ring_is_open_floor() {
+        let mut env = env();
+        env.reset(1, EnvConfig { opponents: Vec::new(), ..EnvConfig::default() }).unwrap();
+        let engine = env.engine.as_mut().unwrap();
+        let snake = &mut engine.state.players.get_mut("agent-0").unwrap().snake;
+        snake.body = (0..3).map(|i| Point { x: 10 - i, y: 0 }).collect();
+        (snake.direction, snake.next_direction) = (Direction::Right, Direction::Right);
+        engine.state.rebuild_occupancy();
+
+        let obs = observe(env.state().unwrap(), "agent-0", None);
+        assert!((0..obs.width).all(|col| !obs.get(0, 0, col)));
+        let local = observe(env.state().unwrap(), "agent-0", Some(1));
+        assert!((0..3).all(|col| local.get(0, 0, col) && !local.get(0, 1, col)));
+
+        let result = env.step(&[None]).unwrap();
+        assert!(result.info.alive[0]);
+        assert_eq!(*env.state().unwrap().players["agent-0"].snake.head(), Point { x: 11, y: 0 });
+    }
+
+    #[test]
+    fn egocentric_views_centre_the_head_and_policies_play_as_bots() {
+        let mut env = env();
+        let config = EnvConfig { view_radius: Some(3), opponents: Vec::new(), ..EnvConfig::default() };
+        let obs = env.reset(1, config).unwrap().observations.remove(0);
+        assert_eq!((obs.channels, obs.height, obs.width), (CHANNELS.len(), 7, 7));
+        assert!(obs.get(1, 3, 3) && obs.get(2, 3, 3));
+        let state = env.state().unwrap();
+        let head = *state.players["agent-0"].snake.head();
+        let food_in_view = state.food.iter().filter(|f| (f.x - head.x).abs() <= 3 && (f.y - head.y).abs() <= 3).count();
+        assert_eq!(obs.data[5 * 49..6 * 49].iter().filter(|&&v| v == 1).count(), food_in_view);
+
+        // A one-layer policy that always scores Left highest (unless that's backwards)
+        let inputs = obs.data.len();
+        let policy = Policy {
+            name: "lefty".to_string(),
+            description: String::new(),
+            view_radius: Some(3),
+            layers: vec![DenseLayer { weights: vec![vec![0.0; inputs]; 4], bias: vec![0.0, 0.0, 1.0, 0.5] }],
+        };
+        assert!(policy.validate().is_ok());
+        assert!(Policy { layers: vec![DenseLayer { weights: vec![vec![0.0; 3]; 2], bias: vec![0.0; 2] }], ..policy.clone() }
+            .validate().is_err());
+
+        let mut registry = BotRegistry::builtin();
+        registry.register_policy(Arc::new(policy.clone()));
+        let mut bot = registry.create(&policy.strategy()).unwrap();
+        let state = env.state().unwrap();
+        let expected = if state.players["agent-0"].snake.direction == Direction::Right { Direction::Right } else { Direction::Left };
+        assert_eq!(bot.decide(state, "agent-0", &mut rand::thread_rng()), Some(expected));
+    }
+
+    #[test]
+    fn socket_sessions_share_the_open_environment_limit() {
+        let envs = Arc::new(GymEnvs::new(Arc::new(BotRegistry::builtin()), Arc::new(MapCatalog::builtin())));
+        let user = |i: usize| format!("user-{}", i % (MAX_OPEN_ENVS / MAX_OPEN_ENVS_PER_USER));
+        let mut leases: Vec<EnvLease> = (0..MAX_OPEN_ENVS / 2).map(|i| envs.lease(&user(i)).unwrap()).collect();
+        let ids: Vec<String> = (0..MAX_OPEN_ENVS / 2).map(|i| envs.open(&user(i)).unwrap()).collect();
+        assert_eq!(envs.open("newcomer"), Err(EnvLimit::Full));
+        assert!(envs.lease("newcomer").is_err());
+
+        leases.pop();
+        leases.push(envs.lease("newcomer").unwrap());
+        assert!(envs.close(&ids[0], &user(0)));
+        assert!(envs.open("newcomer").is_ok());
+        assert!(envs.lease("newcomer").is_err());
+    }
+
+    #[test]
+    fn environments_belong_to_whoever_opened_them() {
+        let envs = Arc::new(GymEnvs::new(Arc::new(BotRegistry::builtin()), Arc::new(MapCatalog::builtin())));
+        let id = envs.open("alice").unwrap();
+        assert!(envs.get(&id, "mallory").is_none());
+        assert!(!envs.close(&id, "mallory"));
+        assert!(envs.get(&id, "alice").is_some());
+
+        // Sockets and HTTP environments count toward the same share
+        let _lease = envs.lease("alice").unwrap();
+        for _ in 2..MAX_OPEN_ENVS_PER_USER {
+            envs.open("alice").unwrap();
+        }
+        assert_eq!(envs.open("alice"), Err(EnvLimit::PerUser));
+        assert!(envs.lease("alice").is_err());
+        assert!(envs.open("mallory").is_ok());
+        assert!(envs.close(&id, "alice"));
+        assert!(envs.open("alice").is_ok());
+    }
+}
@@ -0,0 +1,501 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use super::store::JsonDirStore;
+use super::{Direction, Point, INITIAL_SNAKE_LENGTH};
+
+const MIN_MAP_SIDE: i32 = 10;
+const MAX_MAP_SIDE: i32 = 200;
+const MAX_MAP_CELLS: i32 = 12_000; // Big enough for a full arena, small enough to simulate cheaply
+const MAX_MAP_NAME_LEN: usize = 40;
+const MIN_SPAWN_ROOM: usize = 30;  // Open cells a spawn must be able to reach
+
+// Shipped with the backend; see `GameMap::from_ascii` for the format
+const BUILTIN_MAPS: [(&str, &str); 4] = [
+    ("pillars", include_str!("../../../maps/pillars.txt")),
+    ("crossroads", include_str!("../../../maps/crossroads.txt")),
+    ("portals", include_str!("../../../maps/portals.txt")),
+    ("garden", include_str!("../../../maps/garden.txt")),
+];
+
+// =============================================================================
+// MAP FORMAT
+// =============================================================================
+
+/// Two cells joined both ways: a head entering one comes out of the other
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Portal {
+    pub a: Point,
+    pub b: Point,
+}
+
+/// Seat position; the snake's body trails behind it
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Spawn {
+    pub position: Point,
+    pub direction: Direction,
+}
+
+/// Rectangle where food prefers to appear
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FoodZone {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl FoodZone {
+    pub fn cells(&self) -> impl Iterator<Item = Point> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| Point { x, y }))
+    }
+}
+
+/// A board layout. Cells outside `walls` and `portals` are open floor.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GameMap {
+    pub id: String,
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    #[serde(default)]
+    pub walls: Vec<Point>,
+    #[serde(default)]
+    pub portals: Vec<Portal>,
+    #[serde(default)]
+    pub spawns: Vec<Spawn>, // Seat N uses spawn N; extra seats are placed dynamically
+    #[serde(default)]
+    pub food_zones: Vec<FoodZone>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>, // User id of the uploader; None for built-ins and guests
+}
+
+/// Summary for map pickers
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MapInfo {
+    pub id: String,
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    pub spawns: usize,
+    pub builtin: bool,
+    pub author: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapError {
+    Parse(String),
+    BadName,
+    BadSize { width: i32, height: i32 },
+    OutOfBounds { what: &'static str, at: Point },
+    Blocked { what: &'static str, at: Point }, // Sits on a wall or portal
+    PortalOverlap { at: Point },
+    SpawnOverlap { at: Point },
+    UnreachableSpawn { at: Point }, // Boxed in, or cut off from the other spawns
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Parse(msg) => write!(f, "Could not read map: {}", msg),
+            MapError::BadName => write!(f, "Map names must be 1 to {} characters", MAX_MAP_NAME_LEN),
+            MapError::BadSize { width, height } => write!(
+                f, "Map is {}x{}; each side must be between {} and {}, with at most {} cells",
+                width, height, MIN_MAP_SIDE, MAX_MAP_SIDE, MAX_MAP_CELLS
+            ),
+            MapError::OutOfBounds { what, at } => write!(f, "{} at ({}, {}) is off the map", what, at.x, at.y),
+            MapError::Blocked { what, at } => write!(f, "{} at ({}, {}) is blocked", what, at.x, at.y),
+            MapError::PortalOverlap { at } => write!(f, "More than one portal uses ({}, {})", at.x, at.y),
+            MapError::SpawnOverlap { at } => write!(f, "Two spawns would start on ({}, {})", at.x, at.y),
+            MapError::UnreachableSpawn { at } => {
+                write!(f, "Spawn at ({}, {}) is boxed in or cut off from the others", at.x, at.y)
+            }
+        }
+    }
+}
+
+impl GameMap {
+    /// Read a map from JSON (anything starting with `{`) or ASCII art
+    pub fn parse(id: &str, text: &str) -> Result<GameMap, MapError> {
+        let map = if text.trim_start().starts_with('{') {
+            let mut map: GameMap = serde_json::from_str(text).map_err(|e| MapError::Parse(e.to_string()))?;
+            map.id = id.to_string();
+            map
+        } else {
+            GameMap::from_ascii(id, text)?
+        };
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// One character per cell, one line per row:
+    ///
+    /// - `#` wall, `.` or space open floor, `*` open floor in a food zone
+    /// - `^` `v` `<` `>` spawn facing that way, numbered in reading order
+    /// - `0`-`9` portal ends; each digit must appear exactly twice
+    ///
+    /// Lines starting with `;` are comments, and `; name: ...` names the map.
+    pub fn from_ascii(id: &str, text: &str) -> Result<GameMap, MapError> {
+        let mut name = id.to_string();
+        let mut rows = Vec::new();
+        for line in text.lines() {
+            match line.strip_prefix(';') {
+                Some(comment) => {
+                    if let Some(value) = comment.trim().strip_prefix("name:") {
+                        name = value.trim().to_string();
+                    }
+                }
+                None if line.trim().is_empty() && rows.is_empty() => {} // Leading blank lines
+                None => rows.push(line.trim_end_matches('\r')),
+            }
+        }
+        while rows.last().is_some_and(|row| row.trim().is_empty()) {
+            rows.pop();
+        }
+
+        let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0) as i32;
+        let mut map = GameMap {
+            id: id.to_string(),
+            name,
+            width,
+            height: rows.len() as i32,
+            walls: Vec::new(),
+            portals: Vec::new(),
+            spawns: Vec::new(),
+            food_zones: Vec::new(),
+            author: None,
+        };
+
+        let mut portal_ends: BTreeMap<char, Vec<Point>> = BTreeMap::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let at = Point { x: x as i32, y: y as i32 };
+                let spawn = |direction| Spawn { position: at, direction };
+                match c {
+                    '#' => map.walls.push(at),
+                    '.' | ' ' => {}
+                    '*' => map.food_zones.push(FoodZone { x: at.x, y: at.y, width: 1, height: 1 }),
+                    '0'..='9' => portal_ends.entry(c).or_default().push(at),
+                    '^' => map.spawns.push(spawn(Direction::Up)),
+                    'v' => map.spawns.push(spawn(Direction::Down)),
+                    '<' => map.spawns.push(spawn(Direction::Left)),
+                    '>' => map.spawns.push(spawn(Direction::Right)),
+                    _ => {
+                        return Err(MapError::Parse(format!("unknown character '{}' at ({}, {})", c, x, y)));
+                    }
+                }
+            }
+        }
+
+        for (digit, ends) in portal_ends {
+            match ends.as_slice() {
+                [a, b] => map.portals.push(Portal { a: *a, b: *b }),
+                _ => return Err(MapError::Parse(format!("portal '{}' needs exactly two ends", digit))),
+            }
+        }
+        map.merge_food_zones();
+        Ok(map)
+    }
+
+    /// Join single-cell zones into horizontal runs to keep the JSON small
+    fn merge_food_zones(&mut self) {
+        let mut merged: Vec<FoodZone> = Vec::new();
+        for zone in self.food_zones.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.y == zone.y && last.height == 1 && last.x + last.width == zone.x => {
+                    last.width += zone.width;
+                }
+                _ => merged.push(zone),
+            }
+        }
+        self.food_zones = merged;
+    }
+
+    /// Check everything is on the board, nothing sits where it can't,
+    /// and every spawn can reach the others
+    pub fn validate(&self) -> Result<(), MapError> {
+        let name_len = self.name.trim().chars().count();
+        if name_len == 0 || name_len > MAX_MAP_NAME_LEN {
+            return Err(MapError::BadName);
+        }
+        let size_ok = |side: i32| (MIN_MAP_SIDE..=MAX_MAP_SIDE).contains(&side);
+        if !size_ok(self.width) || !size_ok(self.height) || self.width * self.height > MAX_MAP_CELLS {
+            return Err(MapError::BadSize { width: self.width, height: self.height });
+        }
+        let in_bounds = |what, at: Point| {
+            if at.x < 0 || at.x >= self.width || at.y < 0 || at.y >= self.height {
+                return Err(MapError::OutOfBounds { what, at });
+            }
+            Ok(())
+        };
+
+        let walls: HashSet<Point> = self.walls.iter().copied().collect();
+        for &wall in &self.walls {
+            in_bounds("Wall", wall)?;
+        }
+
+        let mut portal_cells = HashSet::new();
+        for portal in &self.portals {
+            for end in [portal.a, portal.b] {
+                in_bounds("Portal", end)?;
+                if walls.contains(&end) {
+                    return Err(MapError::Blocked { what: "Portal", at: end });
+                }
+                if !portal_cells.insert(end) {
+                    return Err(MapError::PortalOverlap { at: end });
+                }
+            }
+        }
+
+        let mut spawn_cells = HashSet::new();
+        for spawn in &self.spawns {
+            // The whole starting body has to fit on open floor, clear of other spawns
+            for i in 0..INITIAL_SNAKE_LENGTH as i32 {
+                let cell = behind(spawn.position, spawn.direction, i);
+                in_bounds("Spawn", cell)?;
+                if walls.contains(&cell) || portal_cells.contains(&cell) {
+                    return Err(MapError::Blocked { what: "Spawn", at: spawn.position });
+                }
+                if !spawn_cells.insert(cell) {
+                    return Err(MapError::SpawnOverlap { at: cell });
+                }
+            }
+        }
+
+        if let Some(first) = self.spawns.first() {
+            let region = self.reachable_from(first.position, &walls);
+            for spawn in &self.spawns {
+                if region.len() < MIN_SPAWN_ROOM || !region.contains(&spawn.position) {
+                    return Err(MapError::UnreachableSpawn { at: spawn.position });
+                }
+            }
+        }
+
+        for zone in &self.food_zones {
+            for cell in zone.cells() {
+                in_bounds("Food zone", cell)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Cells a snake starting on `start` could ever get to, walking through portals
+    fn reachable_from(&self, start: Point, walls: &HashSet<Point>) -> HashSet<Point> {
+        let exits: HashMap<Point, Point> = self.portals.iter()
+            .flat_map(|portal| [(portal.a, portal.b), (portal.b, portal.a)])
+            .collect();
+        let open = |p: Point| p.x >= 0 && p.x < self.width && p.y >= 0 && p.y < self.height && !walls.contains(&p);
+
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(at) = queue.pop_front() {
+            for dir in [Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+                let next = behind(at, dir.opposite(), 1);
+                let next = exits.get(&next).copied().unwrap_or(next);
+                if open(next) && seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Where a head entering `p` comes out, if `p` is a portal
+    pub fn portal_exit(&self, p: Point) -> Option<Point> {
+        self.portals.iter().find_map(|portal| {
+            if portal.a == p {
+                Some(portal.b)
+            } else if portal.b == p {
+                Some(portal.a)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn info(&self, builtin: bool) -> MapInfo {
+        MapInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            width: self.width,
+            height: self.height,
+            spawns: self.spawns.len(),
+            builtin,
+            author: self.author.clone(),
+        }
+    }
+}
+
+/// `n` cells behind `p` for a snake facing `dir`
+fn behind(p: Point, dir: Direction, n: i32) -> Point {
+    match dir {
+        Direction::Up => Point { x: p.x, y: p.y + n },
+        Direction::Down => Point { x: p.x, y: p.y - n },
+        Direction::Left => Point { x: p.x + n, y: p.y },
+        Direction::Right => Point { x: p.x - n, y: p.y },
+    }
+}
+
+// =============================================================================
+// STORAGE
+// =============================================================================
+
+/// Where uploaded maps live; the filesystem store is the default
+pub trait MapStore: Send + Sync {
+    fn save(&self, map: &GameMap) -> io::Result<()>;
+    fn load(&self, id: &str) -> io::Result<Option<GameMap>>;
+    fn list(&self) -> io::Result<Vec<GameMap>>;
+    fn delete(&self, id: &str) -> io::Result<bool>; // False if there was nothing to delete
+}
+
+/// One JSON file per map under `dir`
+pub type FileMapStore = JsonDirStore<GameMap>;
+
+impl FileMapStore {
+    /// Reads MAP_DIR, defaulting to `./user_maps`
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("MAP_DIR").unwrap_or_else(|_| "user_maps".to_string()))
+    }
+}
+
+impl MapStore for FileMapStore {
+    fn save(&self, map: &GameMap) -> io::Result<()> {
+        self.write(&map.id, map)
+    }
+
+    fn load(&self, id: &str) -> io::Result<Option<GameMap>> {
+        self.read(id)
+    }
+
+    fn list(&self) -> io::Result<Vec<GameMap>> {
+        let mut maps = self.read_all()?;
+        maps.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        Ok(maps)
+    }
+
+    fn delete(&self, id: &str) -> io::Result<bool> {
+        self.remove(id)
+    }
+}
+
+// =============================================================================
+// CATALOG
+// =============================================================================
+
+/// Maps rooms can be created with: the built-ins, then anything in the store
+#[derive(Default)]
+pub struct MapCatalog {
+    builtin: BTreeMap<String, Arc<GameMap>>,
+    store: Option<Arc<dyn MapStore>>,
+}
+
+impl MapCatalog {
+    /// The maps that ship with the backend
+    pub fn builtin() -> Self {
+        let mut catalog = MapCatalog::default();
+        for (id, text) in BUILTIN_MAPS {
+            match GameMap::parse(id, text) {
+                Ok(map) => {
+                    catalog.builtin.insert(map.id.clone(), Arc::new(map));
+                }
+                Err(e) => log::error!("Built-in map '{}' is invalid: {}", id, e),
+            }
+        }
+        catalog
+    }
+
+    /// Built-ins plus uploads kept in `store`
+    pub fn with_store(store: Arc<dyn MapStore>) -> Self {
+        MapCatalog { store: Some(store), ..Self::builtin() }
+    }
+
+    pub fn is_builtin(&self, id: &str) -> bool {
+        self.builtin.contains_key(id)
+    }
+
+    /// Blocking when the map comes from the store
+    pub fn get(&self, id: &str) -> io::Result<Option<Arc<GameMap>>> {
+        if let Some(map) = self.builtin.get(id) {
+            return Ok(Some(map.clone()));
+        }
+        match &self.store {
+            Some(store) => Ok(store.load(id)?.map(Arc::new)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn list(&self) -> io::Result<Vec<MapInfo>> {
+        let mut maps: Vec<MapInfo> = self.builtin.values().map(|map| map.info(true)).collect();
+        if let Some(store) = &self.store {
+            maps.extend(store.list()?.iter().map(|map| map.info(false)));
+        }
+        Ok(maps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_maps_parse_and_validate() {
+        for (id, text) in BUILTIN_MAPS {
+            let map = GameMap::parse(id, text).unwrap_or_else(|e| panic!("{}: {}", id, e));
+            assert!(map.spawns.len() >= 4, "{} has too few spawns", id);
+        }
+        assert_eq!(MapCatalog::builtin().list().unwrap().len(), BUILTIN_MAPS.len());
+    }
+
+    #[test]
+    fn ascii_maps_reject_broken_layouts() {
+        let row = ".".repeat(12);
+        let board = |middle: &str| format!("{}\n{}\n{}\n", row, middle, [row.as_str(); 9].join("\n"));
+
+        let map = GameMap::parse("ok", &board("..>..1....1.")).unwrap();
+        assert_eq!(map.spawns, vec![Spawn { position: Point { x: 2, y: 1 }, direction: Direction::Right }]);
+        assert_eq!(map.portal_exit(Point { x: 5, y: 1 }), Some(Point { x: 10, y: 1 }));
+
+        assert!(matches!(GameMap::parse("x", &board("..>..1......")), Err(MapError::Parse(_))));
+        assert!(matches!(GameMap::parse("x", &board(".>..........")), Err(MapError::OutOfBounds { .. })));
+        assert!(matches!(GameMap::parse("x", &board("#.>.........")), Err(MapError::Blocked { .. })));
+        assert!(matches!(GameMap::parse("x", "....\n....\n"), Err(MapError::BadSize { .. })));
+    }
+
+    #[test]
+    fn uploads_need_reachable_separate_spawns_and_round_trip_through_the_store() {
+        let row = ".".repeat(12);
+        let board = |rows: &[&str]| rows.iter().copied().chain([row.as_str(); 8]).collect::<Vec<_>>().join("\n");
+
+        let overlapping = board(&[row.as_str(), "...^........", ".....>......"]);
+        assert!(matches!(GameMap::parse("x", &overlapping), Err(MapError::SpawnOverlap { .. })));
+
+        // A wall splits the board; portals are the only way across
+        let split = ["......#.....", "..>...#..<..", "......#....."];
+        let walled = format!("{}\n{}", split.join("\n"), ["......#....."; 8].join("\n"));
+        assert!(matches!(GameMap::parse("x", &walled), Err(MapError::UnreachableSpawn { .. })));
+        let bridged = walled.replacen("......#.....", ".....1#1....", 1);
+        assert!(GameMap::parse("x", &bridged).is_ok());
+
+        let huge = format!(">{}", ".".repeat(119)) + &format!("\n{}", ".".repeat(120)).repeat(109);
+        assert!(matches!(GameMap::parse("x", &huge), Err(MapError::BadSize { .. })));
+
+        let dir = std::env::temp_dir().join(format!("chill_space_maps_{}", uuid::Uuid::new_v4()));
+        let store = FileMapStore::new(&dir);
+        let mut map = GameMap::parse("abc-123", &bridged).unwrap();
+        map.author = Some("user-1".to_string());
+        store.save(&map).unwrap();
+        assert_eq!(store.load("abc-123").unwrap().and_then(|m| m.author), Some("user-1".to_string()));
+        assert!(store.load("../abc-123").unwrap().is_none());
+
+        let catalog = MapCatalog::with_store(Arc::new(store));
+        assert!(catalog.get("abc-123").unwrap().is_some());
+        assert_eq!(catalog.list().unwrap().iter().filter(|info| !info.builtin).count(), 1);
+        assert!(FileMapStore::new(&dir).delete("abc-123").unwrap());
+        assert!(catalog.get("abc-123").unwrap().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,2727 @@
+use actix::prelude::*;
+use actix_web_actors::ws;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::auth::{AuthError, Authenticator};
+
+mod bots;
+mod code_bots;
+mod engine;
+mod grid;
+mod gym;
+mod map;
+mod replay;
+mod store;
+mod tournament;
+
+use bots::{DifficultyRatings, DifficultyTracker};
+use grid::{Occupancy, Terrain};
+pub use bots::{BotBrains, BotRegistry, HardWeights, DEFAULT_BOT_STRATEGY};
+pub use code_bots::{BotSource, BotSourceStore, FileBotSourceStore};
+pub use engine::{Engine, GameEvent, Input};
+pub use gym::{
+    observe, parse_actions, Action, EnvConfig, EnvError, EnvLease, EnvLimit, GymEnvs, GymSession, Observation,
+    Policy, RewardConfig, SnakeEnv, StepResult, ACTIONS, CHANNELS,
+};
+pub use map::{FileMapStore, GameMap, MapCatalog, MapStore};
+pub use replay::{FileReplayStore, Replay, ReplaySession, ReplayStore};
+pub use store::JsonDirStore;
+pub use tournament::{Tournament, TournamentReport};
+
+// =============================================================================
+// CONSTANTS
+// =============================================================================
+
+const GRID_WIDTH: i32 = 50;
+const GRID_HEIGHT: i32 = 35;
+const COUNTDOWN_DURATION: u8 = 3; 
+const INTERMISSION_DURATION: u8 = 5; // Seconds between rounds
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_PLAYERS: usize = 4;
+const MAX_ARENA_PLAYERS: usize = 64;
+const ARENA_CELLS_PER_PLAYER: i32 = 150;  // Arena boards grow to keep at least this much room per snake
+const ARENA_FOOD_PER_PLAYER: usize = 2;
+const ARENA_RESPAWN_DELAY: Duration = Duration::from_secs(3);
+const ARENA_ROUND_DURATION: Duration = Duration::from_secs(3 * 60);
+const INITIAL_SNAKE_LENGTH: usize = 3;
+const POWERUP_SPAWN_INTERVAL: Duration = Duration::from_secs(10); 
+const POWERUP_SPEED_DURATION: Duration = Duration::from_secs(5);   
+const POWERUP_SHIELD_DURATION: Duration = Duration::from_secs(3);   
+const POWERUP_GHOST_DURATION: Duration = Duration::from_secs(2);   
+const QUICK_MATCH_BOT_FILL_DELAY: Duration = Duration::from_secs(30);
+const QUICK_MATCH_RESERVATION: Duration = Duration::from_secs(20); // A matched seat is held this long for its player
+const ROOM_REAP_INTERVAL: Duration = Duration::from_secs(15);
+const ROOM_IDLE_GRACE: Duration = Duration::from_secs(120);    // Empty rooms are evicted after this
+const MAX_ROOM_LIFETIME: Duration = Duration::from_secs(3 * 60 * 60);
+const RESUME_GRACE: Duration = Duration::from_secs(30); // How long a dropped player's seat is kept
+const KEYFRAME_INTERVAL: u64 = 40; // Full GameState every N ticks, deltas in between
+const MAX_DELTA_HEADS: usize = 4;  // More new cells than this in one tick forces a keyframe
+
+// Player colors (violet theme palette)
+const PLAYER_COLORS: [&str; 4] = ["#a855f7", "#22d3ee", "#f472b6", "#4ade80"];
+
+/// Seat color: the theme palette first, then hues spread by the golden angle
+pub fn player_color(slot: usize) -> String {
+    if let Some(color) = PLAYER_COLORS.get(slot) {
+        return color.to_string();
+    }
+
+    let hue = (slot as f64 * 137.508) % 360.0;
+    let (saturation, lightness) = (0.7, 0.6);
+    let chroma = (1.0 - (2.0 * lightness - 1.0_f64).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |v: f64| ((v + m) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+}
+
+// =============================================================================
+// ROOM SETTINGS
+// =============================================================================
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum GameSpeed {
+    Slow,   
+    Normal, 
+    Fast,   
+}
+
+impl GameSpeed {
+    pub fn to_millis(&self) -> u64 {
+        match self {
+            GameSpeed::Slow => 200,
+            GameSpeed::Normal => 150,
+            GameSpeed::Fast => 100,
+        }
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_millis(self.to_millis())
+    }
+
+    /// Number of ticks that best approximates `duration` at this speed (at least 1)
+    pub fn ticks_for(&self, duration: Duration) -> u32 {
+        let tick_ms = self.to_millis() as u128;
+        (((duration.as_millis() + tick_ms / 2) / tick_ms) as u32).max(1)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum MapSize {
+    Small,  
+    Medium, 
+    Large,  
+}
+
+impl MapSize {
+    pub fn dimensions(&self) -> (i32, i32) {
+        match self {
+            MapSize::Small => (40, 30),
+            MapSize::Medium => (50, 35),
+            MapSize::Large => (60, 40),
+        }
+    }
+}
+
+/// Classic is last-snake-standing for up to four players; Arena is a timed
+/// free-for-all for dozens, with respawns and a score leaderboard
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    Arena,
+}
+
+impl GameMode {
+    pub fn max_players(&self) -> usize {
+        match self {
+            GameMode::Classic => MAX_PLAYERS,
+            GameMode::Arena => MAX_ARENA_PLAYERS,
+        }
+    }
+}
+
+/// What happens when two snakes' heads meet
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum HeadOnRule {
+    #[default]
+    BothDie,
+    LongerWins, // Equal lengths still both die
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoomSettings {
+    pub max_players: usize,
+    pub speed: GameSpeed,
+    pub power_ups_enabled: bool,
+    pub rounds: u8,
+    pub map_size: MapSize,
+    #[serde(default)]
+    pub head_on: HeadOnRule,
+    #[serde(default)]
+    pub mode: GameMode,
+    #[serde(default)]
+    pub map_id: Option<String>, // Built-in or uploaded layout; None is the open board
+    #[serde(default)]
+    pub wrap_edges: bool, // Toroidal board: leaving one edge enters the opposite one
+    #[serde(default)]
+    pub bot_arena: bool, // Only bots play; people can only watch
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        RoomSettings {
+            max_players: 4,
+            speed: GameSpeed::Normal,
+            power_ups_enabled: true,
+            rounds: 1,
+            map_size: MapSize::Medium,
+            head_on: HeadOnRule::BothDie,
+            mode: GameMode::Classic,
+            map_id: None,
+            wrap_edges: false,
+            bot_arena: false,
+        }
+    }
+}
+
+impl RoomSettings {
+    /// Clamp client-supplied values into ranges the simulation supports
+    pub fn normalized(mut self) -> Self {
+        self.max_players = self.max_players.clamp(1, self.mode.max_players());
+        self.rounds = self.rounds.max(1);
+        self
+    }
+
+    /// Board size; arenas scale the chosen map up to fit `max_players`
+    pub fn dimensions(&self) -> (i32, i32) {
+        let (width, height) = self.map_size.dimensions();
+        if self.mode != GameMode::Arena {
+            return (width, height);
+        }
+        let needed = self.max_players as f64 * ARENA_CELLS_PER_PLAYER as f64;
+        let scale = (needed / (width * height) as f64).sqrt().max(1.0);
+        ((width as f64 * scale).ceil() as i32, (height as f64 * scale).ceil() as i32)
+    }
+}
+
+// =============================================================================
+// GAME TYPES
+// =============================================================================
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Copy)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub fn opposite(&self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum DeathCause {
+    Wall,
+    SelfCollision,
+    Body,   // Ran into another snake
+    HeadOn,
+}
+
+/// How a snake died; `killer` is the other snake involved, if any
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Death {
+    pub cause: DeathCause,
+    pub killer: Option<String>,
+    pub tick: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snake {
+    pub body: VecDeque<Point>, // Head at the front; moving is O(1) at both ends
+    pub direction: Direction,
+    pub next_direction: Direction,
+    pub alive: bool,
+    pub score: u32,
+    pub color: String,
+    pub death: Option<Death>, // Set when `alive` turns false
+}
+
+impl Snake {
+    pub fn new(start: Point, direction: Direction, color: String) -> Self {
+        let mut body = VecDeque::from([start]);
+        for i in 1..INITIAL_SNAKE_LENGTH {
+            let offset = match direction {
+                Direction::Up => Point { x: start.x, y: start.y + i as i32 },
+                Direction::Down => Point { x: start.x, y: start.y - i as i32 },
+                Direction::Left => Point { x: start.x + i as i32, y: start.y },
+                Direction::Right => Point { x: start.x - i as i32, y: start.y },
+            };
+            body.push_back(offset);
+        }
+        Snake {
+            body,
+            direction,
+            next_direction: direction,
+            alive: true,
+            score: 0,
+            color,
+            death: None,
+        }
+    }
+
+    pub fn head(&self) -> &Point {
+        &self.body[0]
+    }
+
+    pub fn set_direction(&mut self, dir: Direction) {
+        if dir != self.direction.opposite() {
+            self.next_direction = dir;
+        }
+    }
+
+    /// Step one cell; returns the tail cell that was vacated
+    pub fn move_forward(&mut self) -> Option<Point> {
+        self.direction = self.next_direction;
+        let head = *self.head();
+        let new_head = match self.direction {
+            Direction::Up => Point { x: head.x, y: head.y - 1 },
+            Direction::Down => Point { x: head.x, y: head.y + 1 },
+            Direction::Left => Point { x: head.x - 1, y: head.y },
+            Direction::Right => Point { x: head.x + 1, y: head.y },
+        };
+        self.body.push_front(new_head);
+        self.body.pop_back()
+    }
+
+    pub fn grow(&mut self) {
+        if let Some(&tail) = self.body.back() {
+            self.body.push_back(tail);
+        }
+        self.score += 10;
+    }
+}
+
+// =============================================================================
+// POWER-UPS
+// =============================================================================
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum PowerUpType {
+    SpeedBoost,   // 2x speed for 5s (two moves per tick)
+    Shield,       // Invincible for 3s
+    Grow,         // +5 length instantly
+    Ghost,        // Pass through walls/snakes for 2s
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PowerUp {
+    pub id: String,
+    pub position: Point,
+    pub power_type: PowerUpType,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActivePowerUp {
+    pub power_type: PowerUpType,
+    pub ticks_remaining: u32, // Ticks until power-up expires
+}
+
+/// Server-side player. Never serialized directly: clients get a `PlayerView`,
+/// and credentials live in `GameRoom::credentials`.
+#[derive(Clone, Debug)]
+pub struct Player {
+    pub id: String,          
+    pub name: String,
+    pub snake: Snake,
+    pub ready: bool,
+    pub active_power: Option<ActivePowerUp>, // Current active power-up
+    pub is_bot: bool,                        // Whether this player is AI-controlled
+    pub strategy: Option<String>,            // Registered bot strategy (if bot)
+    pub round_wins: u8,                      // Rounds won in the current match
+    pub total_score: u32,                    // Score accumulated over finished rounds
+    pub slot: usize,                         // Stable seat index for spawn position and color
+    pub connected: bool,                     // False while a human is inside the resume grace window
+    pub authenticated: bool,                 // Joined with a verified Supabase token
+    pub kills: u32,                          // Snakes killed this match
+    pub deaths: u32,                         // Times died this match
+    pub skill: Option<f32>,                  // Adaptive bots' current skill, 0 to 1
+}
+
+impl Player {
+    /// A human taking seat `slot`, not ready yet, with a fresh snake at `pos`
+    pub fn new(id: String, name: String, slot: usize, pos: Point, dir: Direction) -> Self {
+        Player {
+            id,
+            name,
+            snake: Snake::new(pos, dir, player_color(slot)),
+            ready: false,
+            active_power: None,
+            is_bot: false,
+            strategy: None,
+            round_wins: 0,
+            total_score: 0,
+            slot,
+            connected: true,
+            authenticated: false,
+            kills: 0,
+            deaths: 0,
+            skill: None,
+        }
+    }
+
+    /// A bot playing `strategy` (a `BotRegistry` name) from seat `slot`; bots are always ready
+    pub fn new_bot(id: String, name: String, strategy: String, slot: usize, pos: Point, dir: Direction) -> Self {
+        Player {
+            ready: true,
+            is_bot: true,
+            strategy: Some(strategy),
+            ..Player::new(id, name, slot, pos, dir)
+        }
+    }
+
+    /// Cells moved per tick
+    pub fn pace(&self) -> u32 {
+        match &self.active_power {
+            Some(power) if power.power_type == PowerUpType::SpeedBoost => 2,
+            _ => 1,
+        }
+    }
+
+    /// Shield and Ghost both prevent collision deaths
+    pub fn is_protected(&self) -> bool {
+        self.active_power.as_ref()
+            .is_some_and(|p| p.power_type == PowerUpType::Shield || p.power_type == PowerUpType::Ghost)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum GamePhase {
+    Lobby,
+    Countdown,   // New: 3-2-1-GO countdown
+    Playing,
+    RoundOver,   // Intermission between rounds of a match
+    MatchOver,
+}
+
+/// Supabase identity supplied on Join, used only for saving scores
+#[derive(Clone, Debug)]
+struct Credentials {
+    user_id: String,
+    access_token: String,
+}
+
+/// What clients are allowed to see of a player
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerView {
+    pub id: String,
+    pub name: String,
+    pub snake: Snake,
+    pub ready: bool,
+    pub active_power: Option<ActivePowerUp>,
+    pub is_bot: bool,
+    pub strategy: Option<String>,
+    pub round_wins: u8,
+    pub total_score: u32,
+    pub connected: bool,
+    pub authenticated: bool,
+    pub kills: u32,
+    pub deaths: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skill: Option<f32>,
+}
+
+impl From<&Player> for PlayerView {
+    fn from(player: &Player) -> Self {
+        PlayerView {
+            id: player.id.clone(),
+            name: player.name.clone(),
+            snake: player.snake.clone(),
+            ready: player.ready,
+            active_power: player.active_power.clone(),
+            is_bot: player.is_bot,
+            strategy: player.strategy.clone(),
+            round_wins: player.round_wins,
+            total_score: player.total_score,
+            connected: player.connected,
+            authenticated: player.authenticated,
+            kills: player.kills,
+            deaths: player.deaths,
+            skill: player.skill,
+        }
+    }
+}
+
+/// One row of the match scoreboard
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Standing {
+    pub player_id: String,
+    pub name: String,
+    pub round_wins: u8,
+    pub round_score: u32,
+    pub total_score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub death: Option<Death>, // How the round ended for this player
+}
+
+/// Server-side room state; broadcast as a `GameStateView`
+#[derive(Clone, Debug)]
+pub struct GameState {
+    pub phase: GamePhase,
+    pub players: BTreeMap<String, Player>, // Ordered so simulation steps are deterministic
+    pub food: Vec<Point>,
+    pub grid_width: i32,
+    pub grid_height: i32,
+    pub winner: Option<String>,
+    pub countdown: u8,  // Countdown timer (3, 2, 1, 0), also used for the intermission
+    pub countdown_ticks: u32, // Tick counter for countdown timing
+    pub power_ups: Vec<PowerUp>, // Active power-ups on the grid
+    pub round: u8,       // Current round (1-based, 0 before the first match)
+    pub total_rounds: u8,
+    pub spectators: HashMap<String, String>, // session id -> display name
+    pub tick: u64,       // Simulation tick, shared with GameDelta for gap detection
+    pub round_started_at: u64, // Tick the current round's play began
+    pub mode: GameMode,
+    pub ends_at_tick: Option<u64>, // Arena rounds end on the clock
+    pub wrap_edges: bool,
+    pub head_on: HeadOnRule,
+    pub map: Option<Arc<GameMap>>, // Clients fetch the layout by id, so it isn't in the view
+    occupancy: Occupancy, // Cells taken by snakes and items, kept in step by the engine
+}
+
+/// Wire representation of `GameState` (keyframes)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameStateView {
+    pub phase: GamePhase,
+    pub players: BTreeMap<String, PlayerView>,
+    pub food: Vec<Point>,
+    pub grid_width: i32,
+    pub grid_height: i32,
+    pub winner: Option<String>,
+    pub countdown: u8,
+    pub power_ups: Vec<PowerUp>,
+    pub round: u8,
+    pub total_rounds: u8,
+    pub spectators: Vec<String>, // Display names, sorted
+    pub tick: u64,
+    pub mode: GameMode,
+    pub ends_at_tick: Option<u64>,
+    pub map_id: Option<String>,
+    pub wrap_edges: bool,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameState {
+    pub fn new() -> Self {
+        GameState {
+            phase: GamePhase::Lobby,
+            players: BTreeMap::new(),
+            food: Vec::new(),
+            grid_width: GRID_WIDTH,
+            grid_height: GRID_HEIGHT,
+            winner: None,
+            countdown: 0,
+            countdown_ticks: 0,
+            power_ups: Vec::new(),
+            round: 0,
+            total_rounds: 1,
+            spectators: HashMap::new(),
+            tick: 0,
+            round_started_at: 0,
+            mode: GameMode::Classic,
+            ends_at_tick: None,
+            wrap_edges: false,
+            head_on: HeadOnRule::BothDie,
+            map: None,
+            occupancy: Occupancy::new(GRID_WIDTH, GRID_HEIGHT, false),
+        }
+    }
+
+    /// Recount the grid from scratch after the board was edited directly
+    pub fn rebuild_occupancy(&mut self) {
+        let mut grid = Occupancy::new(self.grid_width, self.grid_height, self.wrap_edges);
+        if let Some(map) = &self.map {
+            map.walls.iter().for_each(|&p| grid.set_terrain(p, Terrain::Wall));
+            for portal in &map.portals {
+                grid.set_terrain(portal.a, Terrain::Portal);
+                grid.set_terrain(portal.b, Terrain::Portal);
+            }
+            map.food_zones.iter().flat_map(|zone| zone.cells()).for_each(|p| grid.add_food_zone(p));
+        }
+        for player in self.players.values() {
+            player.snake.body.iter().for_each(|&p| grid.add_snake(p));
+        }
+        self.food.iter().for_each(|&p| grid.set_item(p, true));
+        self.power_ups.iter().for_each(|pu| grid.set_item(pu.position, true));
+        self.occupancy = grid;
+    }
+
+    /// Seat a player, marking their snake on the grid
+    pub fn add_player(&mut self, player: Player) {
+        self.remove_player(&player.id); // Never leave a stale snake on the grid
+        player.snake.body.iter().for_each(|&p| self.occupancy.add_snake(p));
+        self.players.insert(player.id.clone(), player);
+    }
+
+    /// Remove a player and clear their snake from the grid
+    pub fn remove_player(&mut self, id: &str) -> Option<Player> {
+        let player = self.players.remove(id)?;
+        player.snake.body.iter().for_each(|&p| self.occupancy.remove_snake(p));
+        Some(player)
+    }
+
+    /// True for walls, snake segments and anything off the board (the border
+    /// ring counts as off the board unless the edges wrap)
+    pub fn is_blocked(&self, p: Point) -> bool {
+        let p = self.wrap(p);
+        let off_board = !self.wrap_edges
+            && (p.x <= 0 || p.x >= self.grid_width - 1 || p.y <= 0 || p.y >= self.grid_height - 1);
+        off_board || self.occupancy.is_wall(p) || self.occupancy.snakes_at(p) > 0
+    }
+
+    /// `p` brought back onto the board when the edges wrap; unchanged otherwise
+    pub fn wrap(&self, p: Point) -> Point {
+        if !self.wrap_edges {
+            return p;
+        }
+        Point { x: p.x.rem_euclid(self.grid_width), y: p.y.rem_euclid(self.grid_height) }
+    }
+
+    /// Shortest signed step from `from` to `to` along each axis, across an edge if that's shorter
+    pub fn offset_between(&self, from: &Point, to: &Point) -> (i32, i32) {
+        let shortest = |delta: i32, size: i32| {
+            if !self.wrap_edges {
+                delta
+            } else if delta > size / 2 {
+                delta - size
+            } else if delta < -size / 2 {
+                delta + size
+            } else {
+                delta
+            }
+        };
+        (shortest(to.x - from.x, self.grid_width), shortest(to.y - from.y, self.grid_height))
+    }
+
+    /// Manhattan distance, wrap-aware
+    pub fn distance(&self, a: &Point, b: &Point) -> i32 {
+        let (dx, dy) = self.offset_between(a, b);
+        dx.abs() + dy.abs()
+    }
+
+    /// Spectators' display names in a stable order; session ids stay on the server
+    pub fn spectator_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.spectators.values().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn view(&self) -> GameStateView {
+        GameStateView {
+            phase: self.phase.clone(),
+            players: self.players.iter()
+                .map(|(id, player)| (id.clone(), PlayerView::from(player)))
+                .collect(),
+            food: self.food.clone(),
+            grid_width: self.grid_width,
+            grid_height: self.grid_height,
+            winner: self.winner.clone(),
+            countdown: self.countdown,
+            power_ups: self.power_ups.clone(),
+            round: self.round,
+            total_rounds: self.total_rounds,
+            spectators: self.spectator_names(),
+            tick: self.tick,
+            mode: self.mode,
+            ends_at_tick: self.ends_at_tick,
+            map_id: self.map.as_ref().map(|map| map.id.clone()),
+            wrap_edges: self.wrap_edges,
+        }
+    }
+
+    /// Scoreboard sorted by round wins, then cumulative score (arenas rank by score, then kills)
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self.players.values()
+            .map(|p| Standing {
+                player_id: p.id.clone(),
+                name: p.name.clone(),
+                round_wins: p.round_wins,
+                round_score: p.snake.score,
+                total_score: p.total_score,
+                kills: p.kills,
+                deaths: p.deaths,
+                death: p.snake.death.clone(),
+            })
+            .collect();
+        standings.sort_by(|a, b| {
+            let ranking = match self.mode {
+                GameMode::Classic => b.round_wins.cmp(&a.round_wins).then(b.total_score.cmp(&a.total_score)),
+                GameMode::Arena => b.total_score.cmp(&a.total_score).then(b.kills.cmp(&a.kills)),
+            };
+            ranking.then(a.name.cmp(&b.name))
+        });
+        standings
+    }
+
+    /// Lowest seat index not taken by any player
+    pub fn free_slot(&self) -> usize {
+        (0..).find(|slot| !self.players.values().any(|p| p.slot == *slot)).unwrap()
+    }
+
+    /// The map's spawn for this seat, or one of the four default seats on an open board
+    pub fn fixed_spawn(&self, player_index: usize) -> Option<(Point, Direction)> {
+        if let Some(map) = &self.map {
+            return map.spawns.get(player_index).map(|spawn| (spawn.position, spawn.direction));
+        }
+        let (w, h) = (self.grid_width, self.grid_height);
+        match player_index {
+            0 => Some((Point { x: 5, y: h / 2 }, Direction::Right)),
+            1 => Some((Point { x: w - 6, y: h / 2 }, Direction::Left)),
+            2 => Some((Point { x: w / 2, y: 5 }, Direction::Down)),
+            3 => Some((Point { x: w / 2, y: h - 6 }, Direction::Up)),
+            _ => None,
+        }
+    }
+
+    pub fn get_spawn_position(&self, player_index: usize) -> (Point, Direction) {
+        let (w, h) = (self.grid_width, self.grid_height);
+        match self.fixed_spawn(player_index) {
+            Some(spawn) => spawn,
+            None => {
+                // Lobby seats past the fixed ones: rows of right-facing snakes clear of
+                // the four defaults. Rounds place these dynamically (see `Engine::find_spawn`).
+                let seat = player_index.saturating_sub(4) as i32;
+                let columns = ((w - 20) / 8 + 1).max(1);
+                let rows = ((h - 17) / 3 + 1).max(1);
+                let (column, row) = (seat % columns, seat / columns % rows);
+                (Point { x: 10 + column * 8, y: 8 + row * 3 }, Direction::Right)
+            }
+        }
+    }
+}
+
+// =============================================================================
+// STATE DELTAS
+// =============================================================================
+
+/// Per-tick change to one snake. Apply by prepending `new_cells` to the body,
+/// trimming it to `length - growing`, then padding it to `length` by repeating the last cell.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SnakeDelta {
+    pub player_id: String,
+    pub new_cells: Vec<Point>, // Newest first, usually a single head
+    pub length: usize,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub growing: usize,        // Cells stacked on the tail that it hasn't pulled out yet
+    pub direction: Direction,
+    pub score: u32,
+    pub total_score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub active_power: Option<PowerUpType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub death: Option<Death>, // Only on the tick the snake died
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skill: Option<f32>,   // Adaptive bots only
+}
+
+impl SnakeDelta {
+    pub fn apply(&self, body: &VecDeque<Point>) -> VecDeque<Point> {
+        let mut next: VecDeque<Point> = self.new_cells.iter().chain(body.iter()).copied().collect();
+        next.truncate(self.length.saturating_sub(self.growing).max(1));
+        if let Some(&last) = next.back() {
+            next.resize(self.length, last);
+        }
+        next
+    }
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Incremental update between two consecutive ticks of the same round
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameDelta {
+    pub tick: u64,
+    pub snakes: Vec<SnakeDelta>,
+    pub deaths: Vec<String>,
+    pub food_added: Vec<Point>,
+    pub food_removed: Vec<Point>,
+    pub power_ups_added: Vec<PowerUp>,
+    pub power_ups_removed: Vec<String>,
+}
+
+/// What a delta needs to know about a snake from the tick before
+struct SnakeMark {
+    alive: bool,
+    connected: bool,
+    power: Option<PowerUpType>,
+    front: Vec<Point>, // First few cells, to find them again after the move
+}
+
+/// What a delta needs from the tick before: each snake's front cells, nothing more
+pub struct DeltaBase {
+    tick: u64,
+    snakes: BTreeMap<String, SnakeMark>,
+}
+
+impl DeltaBase {
+    /// Remember `state` before a step; None outside of play
+    pub fn mark(state: &GameState) -> Option<DeltaBase> {
+        (state.phase == GamePhase::Playing).then(|| DeltaBase {
+            tick: state.tick,
+            snakes: state.players.iter()
+                .map(|(id, p)| (id.clone(), SnakeMark {
+                    alive: p.snake.alive,
+                    connected: p.connected,
+                    power: p.active_power.as_ref().map(|power| power.power_type.clone()),
+                    front: p.snake.body.iter().take(MAX_DELTA_HEADS + 1).copied().collect(),
+                }))
+                .collect(),
+        })
+    }
+
+    /// Describe the step from the marked state to `next` using its events, or None if a
+    /// keyframe is needed (phase change, players joining/leaving, respawns, ...)
+    pub fn delta(&self, next: &GameState, events: &[GameEvent]) -> Option<GameDelta> {
+        if next.phase != GamePhase::Playing || next.tick != self.tick + 1 || next.players.len() != self.snakes.len() {
+            return None;
+        }
+
+        let mut snakes = Vec::new();
+        let mut deaths = Vec::new();
+        for (id, player) in &next.players {
+            let mark = self.snakes.get(id)?;
+            let power = player.active_power.as_ref().map(|p| p.power_type.clone());
+            if player.connected != mark.connected || (player.snake.alive && !mark.alive) {
+                return None;
+            }
+            // Dead snakes stay put until the next round or a respawn; only a power-up can wear off
+            if !mark.alive && power == mark.power {
+                continue;
+            }
+            if mark.alive && !player.snake.alive {
+                deaths.push(id.clone());
+            }
+
+            // The old front shows up again behind however many cells were added
+            let body = &player.snake.body;
+            let moved = (0..(MAX_DELTA_HEADS + 1).min(body.len()))
+                .find(|&k| body.iter().skip(k).zip(&mark.front).all(|(a, b)| a == b))?;
+            snakes.push(SnakeDelta {
+                player_id: id.clone(),
+                new_cells: body.iter().take(moved).copied().collect(),
+                length: body.len(),
+                growing: body.iter().rev().zip(body.iter().rev().skip(1)).take_while(|(a, b)| a == b).count(),
+                direction: player.snake.direction,
+                score: player.snake.score,
+                total_score: player.total_score,
+                kills: player.kills,
+                deaths: player.deaths,
+                active_power: power,
+                death: player.snake.death.clone(),
+                skill: player.skill,
+            });
+        }
+
+        // Items spawned and taken within the same step never reach the client
+        let mut delta = GameDelta {
+            tick: next.tick,
+            snakes,
+            deaths,
+            food_added: Vec::new(),
+            food_removed: Vec::new(),
+            power_ups_added: Vec::new(),
+            power_ups_removed: Vec::new(),
+        };
+        for event in events {
+            match event {
+                GameEvent::FoodSpawned { at } => delta.food_added.push(*at),
+                GameEvent::FoodEaten { at, .. } => {
+                    if let Some(i) = delta.food_added.iter().position(|f| f == at) {
+                        delta.food_added.swap_remove(i);
+                    } else if !delta.food_removed.contains(at) {
+                        delta.food_removed.push(*at);
+                    }
+                }
+                GameEvent::PowerUpSpawned { power_up } => delta.power_ups_added.push(power_up.clone()),
+                GameEvent::PowerUpCollected { power_up_id, .. } => {
+                    if let Some(i) = delta.power_ups_added.iter().position(|pu| pu.id == *power_up_id) {
+                        delta.power_ups_added.swap_remove(i);
+                    } else if !delta.power_ups_removed.contains(power_up_id) {
+                        delta.power_ups_removed.push(power_up_id.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(delta)
+    }
+}
+
+impl GameStateView {
+    /// Apply a delta the way clients do; false if it doesn't follow on from this state
+    /// and a keyframe is needed (send `Resync`)
+    pub fn apply(&mut self, delta: &GameDelta) -> bool {
+        if delta.tick != self.tick + 1 {
+            return false;
+        }
+        for change in &delta.snakes {
+            let Some(player) = self.players.get_mut(&change.player_id) else {
+                return false;
+            };
+            player.snake.body = change.apply(&player.snake.body);
+            player.snake.direction = change.direction;
+            player.snake.score = change.score;
+            player.snake.death = change.death.clone();
+            player.total_score = change.total_score;
+            player.kills = change.kills;
+            player.deaths = change.deaths;
+            player.skill = change.skill;
+            player.active_power = match (&change.active_power, player.active_power.take()) {
+                (Some(kind), Some(active)) if active.power_type == *kind => Some(active),
+                (Some(kind), _) => Some(ActivePowerUp { power_type: kind.clone(), ticks_remaining: 0 }),
+                (None, _) => None,
+            };
+        }
+        for id in &delta.deaths {
+            if let Some(player) = self.players.get_mut(id) {
+                player.snake.alive = false;
+            }
+        }
+        self.food.retain(|f| !delta.food_removed.contains(f));
+        self.food.extend(delta.food_added.iter().copied());
+        self.power_ups.retain(|pu| !delta.power_ups_removed.contains(&pu.id));
+        self.power_ups.extend(delta.power_ups_added.iter().cloned());
+        self.tick = delta.tick;
+        true
+    }
+}
+
+// =============================================================================
+// MOVEMENT
+// =============================================================================
+
+impl GameState {
+    /// Get next position given current position and direction, following edges and portals
+    fn next_position(&self, pos: &Point, dir: Direction) -> Point {
+        let next = match dir {
+            Direction::Up => Point { x: pos.x, y: pos.y - 1 },
+            Direction::Down => Point { x: pos.x, y: pos.y + 1 },
+            Direction::Left => Point { x: pos.x - 1, y: pos.y },
+            Direction::Right => Point { x: pos.x + 1, y: pos.y },
+        };
+        let next = self.wrap(next);
+        self.portal_exit(next).unwrap_or(next)
+    }
+
+    /// Where a head entering `p` comes out, if `p` is a portal
+    pub fn portal_exit(&self, p: Point) -> Option<Point> {
+        if self.occupancy.terrain_at(p) != Terrain::Portal {
+            return None;
+        }
+        self.map.as_ref()?.portal_exit(p)
+    }
+}
+
+// =============================================================================
+// MESSAGES
+// =============================================================================
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", content = "payload")]
+pub enum ClientMessage {
+    Join { 
+        name: String,
+        user_id: Option<String>,      // Optional; must match the token's `sub` if sent
+        access_token: Option<String>  // Supabase JWT, omit to play as a guest
+    },
+    Ready,
+    Resume { token: String },
+    Spectate { name: Option<String> },
+    Direction { direction: Direction },
+    StartGame,
+    Restart,
+    PlayAgain, 
+    Resync,    // Client missed a delta and needs a fresh keyframe
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", content = "payload")]
+pub enum ServerMessage {
+    Welcome { player_id: String, resume_token: String },
+    GameState(GameStateView), // Keyframe
+    Delta(GameDelta),
+    PlayerJoined { player_id: String, name: String },
+    PlayerLeft { player_id: String },
+    Error { message: String },
+    AuthFailed { code: String, message: String },
+    GameStarted,
+    RoundOver { round: u8, winner: Option<String>, standings: Vec<Standing> },
+    MatchOver { winner: Option<String>, standings: Vec<Standing>, replay_id: Option<String> },
+    ReplayStatus { step: u32, total_steps: u32, playing: bool, speed: f32 },
+}
+
+// =============================================================================
+// SUPABASE HELPERS
+// =============================================================================
+
+#[derive(Serialize)]
+struct ScoreSubmission {
+    user_id: String,
+    game_id: String,
+    score: u32,
+    created_at: String, // ISO 8601
+}
+
+fn save_score(user_id: &str, score: u32, token: &str) {
+    let supabase_url = std::env::var("SUPABASE_URL").unwrap_or_default();
+    let supabase_key = std::env::var("SUPABASE_KEY").unwrap_or_default();
+    
+    if supabase_url.is_empty() || supabase_key.is_empty() {
+        log::warn!("Supabase credentials not configured, skipping score save.");
+        return;
+    }
+
+    let url = format!("{}/rest/v1/game_scores", supabase_url);
+    let submission = ScoreSubmission {
+        user_id: user_id.to_string(),
+        game_id: "snake-battle".to_string(),
+        score,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let client = reqwest::Client::new();
+    let token = token.to_string();
+    
+    // Spawn async task to avoid blocking the actor
+    actix_web::rt::spawn(async move {
+        match client.post(&url)
+            .header("apikey", supabase_key)
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "return=minimal")
+            .json(&submission)
+            .send()
+            .await 
+        {
+            Ok(res) => {
+                if res.status().is_success() {
+                    log::info!("Score saved for user {}", submission.user_id);
+                } else {
+                    log::error!("Failed to save score: {}", res.status());
+                }
+            },
+            Err(e) => log::error!("Error saving score: {}", e),
+        }
+    });
+}
+
+
+// =============================================================================
+// GAME ROOM ACTOR
+// =============================================================================
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Connect {
+    pub addr: Addr<SnakeSession>,
+    pub id: String,
+    pub spectator: bool, // Connected with ?role=spectator
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Disconnect {
+    pub id: String,
+    pub addr: Addr<SnakeSession>, // Ignored unless this is still the player's current socket
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ClientAction {
+    pub id: String,
+    pub msg: ClientMessage,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SpawnBot {
+    pub strategy: String,     // Name in the room's `BotRegistry`
+    pub name: Option<String>, // Defaults to "<strategy> Bot"
+}
+
+/// Top up a lobby with bots until it reaches `max_players`
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FillWithBots {
+    pub strategy: String,
+}
+
+// =============================================================================
+// ROOM MANAGER - Handles multiple game rooms
+// =============================================================================
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub code: String,
+    pub owner_name: Option<String>,
+    pub player_count: usize,
+    pub max_players: usize,
+    pub settings: RoomSettings,
+    pub status: String,
+    pub spectators: Vec<String>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Option<(String, Addr<GameRoom>)>")]
+pub struct CreateRoom {
+    pub settings: RoomSettings,
+    pub is_public: bool,
+    pub map: Option<Arc<GameMap>>, // Looked up from `settings.map_id` by the caller
+    pub bots: Option<Arc<BotRegistry>>, // Strategies for this room only, e.g. with uploaded programs
+}
+
+#[derive(Message)]
+#[rtype(result = "Option<Addr<GameRoom>>")]
+pub struct JoinRoom {
+    pub code: String,
+}
+
+pub struct QuickMatchResult {
+    pub code: String,
+    pub created: bool, // false when an existing public lobby was joined
+}
+
+#[derive(Message)]
+#[rtype(result = "Option<QuickMatchResult>")]
+pub struct QuickMatch;
+
+/// Sent by a GameRoom whenever its phase, player count or owner changes
+#[derive(Message, Clone, PartialEq)]
+#[rtype(result = "()")]
+pub struct RoomStatusUpdate {
+    pub room_id: String,
+    pub phase: GamePhase,
+    pub player_count: usize,
+    pub owner_name: Option<String>,
+    pub connected: usize, // Open sockets, 0 means the room is idle
+    pub spectators: Vec<String>,
+}
+
+/// Sent by a GameRoom when its actor stops
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RoomClosed {
+    pub room_id: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubscribeLobby {
+    pub id: String,
+    pub addr: Recipient<WsMessage>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UnsubscribeLobby {
+    pub id: String,
+}
+
+/// Ask a GameRoom to kick everyone and stop
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseRoom {
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoomManagerStats {
+    pub live: usize,
+    pub idle: usize,
+    pub reaped: u64,
+}
+
+#[derive(Message)]
+#[rtype(result = "RoomManagerStats")]
+pub struct GetRoomStats;
+
+/// Pushed to room-browser subscribers
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", content = "payload")]
+pub enum LobbyEvent {
+    Snapshot { rooms: Vec<RoomInfo> },
+    RoomCreated(RoomInfo),
+    RoomUpdated(RoomInfo),
+    RoomClosed { code: String },
+}
+
+#[derive(Message)]
+#[rtype(result = "Vec<RoomInfo>")]
+pub struct ListRooms;
+
+/// What the manager knows about a live room, kept fresh by `RoomStatusUpdate`
+pub struct RoomEntry {
+    pub addr: Addr<GameRoom>,
+    pub code: String,
+    pub is_public: bool,
+    pub settings: RoomSettings,
+    pub phase: GamePhase,
+    pub player_count: usize,
+    pub owner_name: Option<String>,
+    pub idle_since: Option<Instant>, // Set while nobody is connected
+    pub spectators: Vec<String>,
+    pub reserved: Vec<Instant>,      // Quick-match seats handed out but not taken yet
+    pub bot_fill: Option<String>,    // Strategy to top the lobby up with, armed by the first join
+}
+
+impl RoomEntry {
+    fn is_joinable(&self) -> bool {
+        self.is_public
+            && !self.settings.bot_arena
+            && self.phase == GamePhase::Lobby
+            && self.player_count + self.reserved.len() < self.settings.max_players
+    }
+
+    fn expire_reservations(&mut self) {
+        self.reserved.retain(|at| at.elapsed() < QUICK_MATCH_RESERVATION);
+    }
+
+    fn status(&self) -> &'static str {
+        match self.phase {
+            GamePhase::Lobby if self.player_count >= self.settings.max_players => "full",
+            GamePhase::Lobby => "waiting",
+            GamePhase::Countdown | GamePhase::Playing | GamePhase::RoundOver => "in_game",
+            GamePhase::MatchOver => "finished",
+        }
+    }
+
+    pub fn info(&self) -> RoomInfo {
+        RoomInfo {
+            code: self.code.clone(),
+            owner_name: self.owner_name.clone(),
+            player_count: self.player_count,
+            max_players: self.settings.max_players,
+            settings: self.settings.clone(),
+            status: self.status().to_string(),
+            spectators: self.spectators.clone(),
+        }
+    }
+}
+
+pub struct RoomManager {
+    pub rooms: HashMap<String, RoomEntry>,
+    pub room_codes: HashMap<String, String>, // code -> room_id
+    pub bot_fill_delay: Duration,             // Quick-match lobbies get bots after this wait
+    pub lobby_subscribers: HashMap<String, Recipient<WsMessage>>,
+    pub reaped_rooms: u64,
+    pub auth: Arc<Authenticator>, // Shared with every room for verifying joins
+    pub replays: Option<Arc<dyn ReplayStore>>, // Finished matches are recorded here if set
+    pub bots: Arc<BotRegistry>, // Strategies rooms create bots from
+    pub ratings: Arc<DifficultyRatings>, // Adaptive bots' skill per signed-in player, across rooms
+}
+
+impl Default for RoomManager {
+    fn default() -> Self {
+        Self::new(QUICK_MATCH_BOT_FILL_DELAY)
+    }
+}
+
+impl RoomManager {
+    pub fn new(bot_fill_delay: Duration) -> Self {
+        RoomManager {
+            rooms: HashMap::new(),
+            room_codes: HashMap::new(),
+            bot_fill_delay,
+            lobby_subscribers: HashMap::new(),
+            reaped_rooms: 0,
+            auth: Arc::new(Authenticator::disabled()),
+            replays: None,
+            bots: Arc::new(BotRegistry::builtin()),
+            ratings: Arc::new(DifficultyRatings::default()),
+        }
+    }
+
+    /// Forget a room and tell the room browser; returns false if it was already gone
+    fn remove_room(&mut self, room_id: &str) -> bool {
+        let Some(entry) = self.rooms.remove(room_id) else {
+            return false;
+        };
+        self.room_codes.remove(&entry.code);
+        if entry.is_public {
+            self.broadcast_lobby(LobbyEvent::RoomClosed { code: entry.code });
+        }
+        true
+    }
+
+    /// Evict rooms that have been empty for longer than the grace period
+    fn reap_idle_rooms(&mut self) {
+        let expired: Vec<String> = self.rooms.iter()
+            .filter(|(_, entry)| entry.idle_since.is_some_and(|t| t.elapsed() >= ROOM_IDLE_GRACE))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for room_id in expired {
+            if let Some(entry) = self.rooms.get(&room_id) {
+                entry.addr.do_send(CloseRoom { reason: "Room closed after being idle".to_string() });
+            }
+            if self.remove_room(&room_id) {
+                self.reaped_rooms += 1;
+                log::info!("Reaped idle room: {}", room_id);
+            }
+        }
+    }
+
+    fn public_rooms(&self) -> Vec<RoomInfo> {
+        let mut rooms: Vec<RoomInfo> = self.rooms.values()
+            .filter(|entry| entry.is_public)
+            .map(|entry| entry.info())
+            .collect();
+        rooms.sort_by(|a, b| b.player_count.cmp(&a.player_count).then(a.code.cmp(&b.code)));
+        rooms
+    }
+
+    fn broadcast_lobby(&self, event: LobbyEvent) {
+        if self.lobby_subscribers.is_empty() {
+            return;
+        }
+        let msg_str = serde_json::to_string(&event).unwrap();
+        for addr in self.lobby_subscribers.values() {
+            addr.do_send(WsMessage(msg_str.clone()));
+        }
+    }
+}
+
+impl Actor for RoomManager {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(ROOM_REAP_INTERVAL, |act, _ctx| {
+            act.reap_idle_rooms();
+        });
+    }
+}
+
+impl Handler<CreateRoom> for RoomManager {
+    type Result = Option<(String, Addr<GameRoom>)>;
+
+    fn handle(&mut self, msg: CreateRoom, ctx: &mut Self::Context) -> Self::Result {
+        let room_id = Uuid::new_v4().to_string();
+        let room_code = GameRoom::generate_room_code();
+        
+        // Ensure unique code
+        let mut code = room_code.clone();
+        while self.room_codes.contains_key(&code) {
+            code = GameRoom::generate_room_code();
+        }
+        
+        let mut settings = msg.settings.normalized();
+        settings.map_id = msg.map.as_ref().map(|map| map.id.clone());
+        let mut room = GameRoom::new(room_id.clone(), code.clone(), settings.clone(), msg.is_public);
+        room.engine.set_map(msg.map);
+        room.manager = Some(ctx.address());
+        room.auth = self.auth.clone();
+        room.replays = self.replays.clone();
+        room.ratings = self.ratings.clone();
+        room.bots = BotBrains::new(msg.bots.unwrap_or_else(|| self.bots.clone()));
+        let room_addr = room.start();
+        
+        let entry = RoomEntry {
+            addr: room_addr.clone(),
+            code: code.clone(),
+            is_public: msg.is_public,
+            settings,
+            phase: GamePhase::Lobby,
+            player_count: 0,
+            owner_name: None,
+            idle_since: Some(Instant::now()), // Nobody has connected yet
+            spectators: Vec::new(),
+            reserved: Vec::new(),
+            bot_fill: None,
+        };
+        if entry.is_public {
+            self.broadcast_lobby(LobbyEvent::RoomCreated(entry.info()));
+        }
+        self.rooms.insert(room_id.clone(), entry);
+        self.room_codes.insert(code.clone(), room_id);
+        
+        log::info!("Created room with code: {}", code);
+        Some((code, room_addr))
+    }
+}
+
+impl Handler<JoinRoom> for RoomManager {
+    type Result = Option<Addr<GameRoom>>;
+
+    fn handle(&mut self, msg: JoinRoom, _ctx: &mut Self::Context) -> Self::Result {
+        let code = msg.code.to_uppercase();
+        if let Some(room_id) = self.room_codes.get(&code) {
+            if let Some(entry) = self.rooms.get(room_id) {
+                log::info!("Player joining room: {}", code);
+                return Some(entry.addr.clone());
+            }
+        }
+        log::warn!("Room not found: {}", code);
+        None
+    }
+}
+
+impl Handler<QuickMatch> for RoomManager {
+    type Result = Option<QuickMatchResult>;
+
+    fn handle(&mut self, _msg: QuickMatch, ctx: &mut Self::Context) -> Self::Result {
+        // Prefer the fullest public lobby that still has a free slot, and hold the
+        // slot so the next quick match can't be sent to the same one
+        self.rooms.values_mut().for_each(RoomEntry::expire_reservations);
+        let best = self.rooms.values_mut()
+            .filter(|entry| entry.is_joinable())
+            .max_by_key(|entry| entry.player_count + entry.reserved.len());
+
+        if let Some(entry) = best {
+            entry.reserved.push(Instant::now());
+            log::info!("Quick match joined room: {}", entry.code);
+            return Some(QuickMatchResult {
+                code: entry.code.clone(),
+                created: false,
+            });
+        }
+
+        let (code, _) = self.handle(CreateRoom {
+            settings: RoomSettings::default(),
+            is_public: true,
+            map: None,
+            bots: None,
+        }, ctx)?;
+
+        // If nobody else shows up in time after the first player joins, they play bots instead
+        let room_id = self.room_codes.get(&code)?;
+        if let Some(entry) = self.rooms.get_mut(room_id) {
+            entry.reserved.push(Instant::now());
+            entry.bot_fill = Some(DEFAULT_BOT_STRATEGY.to_string());
+        }
+
+        Some(QuickMatchResult { code, created: true })
+    }
+}
+
+impl Handler<RoomStatusUpdate> for RoomManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: RoomStatusUpdate, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(entry) = self.rooms.get_mut(&msg.room_id) {
+            // Newcomers take the oldest reserved seats first
+            let joined = msg.player_count.saturating_sub(entry.player_count);
+            entry.reserved.drain(..joined.min(entry.reserved.len()));
+            if msg.player_count > 0 {
+                if let Some(strategy) = entry.bot_fill.take() {
+                    let addr = entry.addr.clone();
+                    ctx.run_later(self.bot_fill_delay, move |_act, _ctx| {
+                        addr.do_send(FillWithBots { strategy });
+                    });
+                }
+            }
+            entry.phase = msg.phase;
+            entry.player_count = msg.player_count;
+            entry.owner_name = msg.owner_name;
+            entry.spectators = msg.spectators;
+            if msg.connected == 0 {
+                entry.idle_since.get_or_insert_with(Instant::now);
+            } else {
+                entry.idle_since = None;
+            }
+            if entry.is_public {
+                let info = entry.info();
+                self.broadcast_lobby(LobbyEvent::RoomUpdated(info));
+            }
+        }
+    }
+}
+
+impl Handler<RoomClosed> for RoomManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: RoomClosed, _ctx: &mut Self::Context) -> Self::Result {
+        if self.remove_room(&msg.room_id) {
+            log::info!("Closed room: {}", msg.room_id);
+        }
+    }
+}
+
+impl Handler<GetRoomStats> for RoomManager {
+    type Result = MessageResult<GetRoomStats>;
+
+    fn handle(&mut self, _msg: GetRoomStats, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(RoomManagerStats {
+            live: self.rooms.len(),
+            idle: self.rooms.values().filter(|entry| entry.idle_since.is_some()).count(),
+            reaped: self.reaped_rooms,
+        })
+    }
+}
+
+impl Handler<ListRooms> for RoomManager {
+    type Result = Vec<RoomInfo>;
+
+    fn handle(&mut self, _msg: ListRooms, _ctx: &mut Self::Context) -> Self::Result {
+        self.public_rooms()
+    }
+}
+
+impl Handler<SubscribeLobby> for RoomManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeLobby, _ctx: &mut Self::Context) -> Self::Result {
+        let snapshot = LobbyEvent::Snapshot { rooms: self.public_rooms() };
+        msg.addr.do_send(WsMessage(serde_json::to_string(&snapshot).unwrap()));
+        self.lobby_subscribers.insert(msg.id, msg.addr);
+    }
+}
+
+impl Handler<UnsubscribeLobby> for RoomManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnsubscribeLobby, _ctx: &mut Self::Context) -> Self::Result {
+        self.lobby_subscribers.remove(&msg.id);
+    }
+}
+
+// =============================================================================
+// GAME ROOM
+// =============================================================================
+
+pub struct GameRoom {
+    pub room_id: String,
+    pub room_code: String,
+    pub owner_id: Option<String>,
+    pub sessions: HashMap<String, Addr<SnakeSession>>,
+    pub engine: Engine,                 // Rules and state; the room only feeds it inputs
+    pub game_loop: Option<SpawnHandle>, // Running tick interval, if any
+    pub is_public: bool,
+    pub manager: Option<Addr<RoomManager>>,
+    last_reported: Option<RoomStatusUpdate>, // Last status sent to the manager
+    resume_tokens: HashMap<String, String>,  // token -> player id
+    resumed: HashMap<String, String>,        // socket id -> player id it took over with `Resume`
+    resume_grace: Duration,                  // How long a dropped player's seat is kept
+    pending_removals: HashMap<String, SpawnHandle>, // Grace timers for dropped players
+    ticks_since_keyframe: u64,
+    credentials: HashMap<String, Credentials>, // player id -> Supabase identity, never broadcast
+    pending_inputs: Vec<Input>,                // Direction changes received since the last tick
+    recording: Option<Replay>,                 // Match in progress, saved when it ends
+    pub auth: Arc<Authenticator>,
+    pub replays: Option<Arc<dyn ReplayStore>>,
+    pub bots: BotBrains, // Bot players' strategies, and stand-ins for dropped humans
+    difficulty: DifficultyTracker, // Skill of adaptive bots, from how the humans have done here
+    rated_user: Option<String>,    // Signed-in human whose rating `difficulty` carries on
+    pub ratings: Arc<DifficultyRatings>,
+}
+
+impl GameRoom {
+    pub fn new(room_id: String, room_code: String, settings: RoomSettings, is_public: bool) -> Self {
+        GameRoom {
+            room_id,
+            room_code,
+            owner_id: None,
+            sessions: HashMap::new(),
+            engine: Engine::new(rand::random(), settings),
+            game_loop: None,
+            is_public,
+            manager: None,
+            last_reported: None,
+            resume_tokens: HashMap::new(),
+            resumed: HashMap::new(),
+            resume_grace: RESUME_GRACE,
+            pending_removals: HashMap::new(),
+            ticks_since_keyframe: 0,
+            credentials: HashMap::new(),
+            pending_inputs: Vec::new(),
+            recording: None,
+            auth: Arc::new(Authenticator::disabled()),
+            replays: None,
+            bots: BotBrains::new(Arc::new(BotRegistry::builtin())),
+            difficulty: DifficultyTracker::default(),
+            rated_user: None,
+            ratings: Arc::new(DifficultyRatings::default()),
+        }
+    }
+
+    pub fn generate_room_code() -> String {
+        const CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+        let mut rng = rand::thread_rng();
+        (0..6)
+            .map(|_| {
+                let idx = rng.gen_range(0..CHARS.len());
+                CHARS[idx] as char
+            })
+            .collect()
+    }
+
+    fn broadcast(&self, msg: ServerMessage) {
+        let msg_str = serde_json::to_string(&msg).unwrap();
+        for addr in self.sessions.values() {
+            addr.do_send(WsMessage(msg_str.clone()));
+        }
+    }
+
+    fn send_to(&self, id: &str, msg: ServerMessage) {
+        if let Some(addr) = self.sessions.get(id) {
+            let msg_str = serde_json::to_string(&msg).unwrap();
+            addr.do_send(WsMessage(msg_str));
+        }
+    }
+
+    /// Tell the RoomManager about phase/player-count/owner changes (no-op if nothing changed)
+    fn report_status(&mut self) {
+        let status = RoomStatusUpdate {
+            room_id: self.room_id.clone(),
+            phase: self.engine.state.phase.clone(),
+            player_count: self.engine.state.players.len(),
+            owner_name: self.owner_id.as_ref()
+                .and_then(|id| self.engine.state.players.get(id))
+                .map(|p| p.name.clone()),
+            connected: self.sessions.len(),
+            spectators: self.engine.state.spectator_names(),
+        };
+        if self.last_reported.as_ref() == Some(&status) {
+            return;
+        }
+        if let Some(manager) = &self.manager {
+            manager.do_send(status.clone());
+        }
+        self.last_reported = Some(status);
+    }
+
+    /// Turn the identity claimed on Join into verified credentials (None = guest)
+    fn authenticate(&self, user_id: Option<String>, access_token: Option<String>) -> Result<Option<Credentials>, AuthError> {
+        let Some(access_token) = access_token else {
+            // Claiming an identity without proving it is a tampered join
+            return match user_id {
+                Some(_) => Err(AuthError::MissingToken),
+                None => Ok(None),
+            };
+        };
+
+        if !self.auth.is_enabled() {
+            log::warn!("Token verification not configured, player joins room {} as a guest", self.room_code);
+            return Ok(None);
+        }
+
+        let user = self.auth.verify_as(&access_token, user_id.as_deref())?;
+        Ok(Some(Credentials { user_id: user.user_id, access_token }))
+    }
+
+    /// Spawn a bot player running a registered strategy; false if the room is full
+    /// or there's no such strategy
+    pub fn spawn_bot(&mut self, strategy: &str, name: Option<String>) -> bool {
+        if self.engine.state.players.len() >= self.engine.settings.max_players {
+            return false; // Room is full
+        }
+        let Some(strategy) = self.bots.registry().resolve(strategy).map(str::to_string) else {
+            log::warn!("Room {} asked for unknown bot strategy '{}'", self.room_code, strategy);
+            return false;
+        };
+
+        let bot_id = format!("bot_{}", Uuid::new_v4());
+        let bot_name = name.unwrap_or_else(|| format!("{} Bot", strategy));
+
+        let slot = self.engine.state.free_slot();
+        let (pos, dir) = self.engine.state.get_spawn_position(slot);
+        let bot_player = Player::new_bot(bot_id.clone(), bot_name.clone(), strategy, slot, pos, dir);
+
+        if let Some(recording) = &mut self.recording {
+            recording.record_join(&bot_player);
+        }
+        self.engine.add_player(bot_player);
+        self.difficulty.apply(&mut self.engine.state);
+        self.bots.warm_up(&self.engine.state);
+        self.broadcast(ServerMessage::PlayerJoined { 
+            player_id: bot_id, 
+            name: bot_name 
+        });
+        self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+        true
+    }
+
+    fn tick(&mut self) {
+        let mut inputs = std::mem::take(&mut self.pending_inputs);
+
+        // Bots (and dropped humans) decide their next direction
+        if self.engine.state.phase == GamePhase::Playing {
+            self.difficulty.apply(&mut self.engine.state);
+            inputs.extend(self.bots.decide_all(&self.engine.state, &mut rand::thread_rng()));
+        }
+
+        let base = DeltaBase::mark(&self.engine.state);
+        if let Some(recording) = &mut self.recording {
+            recording.record_step(&inputs);
+        }
+        let events = self.engine.step(&inputs);
+
+        // Deltas between periodic keyframes; anything a delta can't express gets a keyframe
+        let mut keyframe = false;
+        if let Some(base) = base {
+            self.ticks_since_keyframe += 1;
+            if self.ticks_since_keyframe < KEYFRAME_INTERVAL {
+                match base.delta(&self.engine.state, &events) {
+                    Some(delta) => self.broadcast(ServerMessage::Delta(delta)),
+                    None => keyframe = true,
+                }
+            } else {
+                keyframe = true;
+            }
+        }
+        keyframe |= self.handle_events(events);
+        if keyframe {
+            self.ticks_since_keyframe = 0;
+            self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+        }
+        self.report_status();
+    }
+
+    /// Adaptive bots start from the first signed-in human's rating from earlier games
+    fn seed_difficulty(&mut self, player_id: &str) {
+        if self.rated_user.is_some() {
+            return;
+        }
+        if let Some(creds) = self.credentials.get(player_id) {
+            self.difficulty = self.ratings.tracker(&creds.user_id);
+            self.rated_user = Some(creds.user_id.clone());
+            self.difficulty.apply(&mut self.engine.state);
+        }
+    }
+
+    /// Fold a finished match into the rating and keep it for that human's next game
+    fn record_difficulty(&mut self) {
+        self.difficulty.record_match(&self.engine.state);
+        if let Some(user_id) = &self.rated_user {
+            self.ratings.save(user_id, &self.difficulty);
+        }
+        self.difficulty.apply(&mut self.engine.state);
+    }
+
+    /// Relay engine events to clients; returns true if they call for a keyframe
+    fn handle_events(&mut self, events: Vec<GameEvent>) -> bool {
+        let mut keyframe = false;
+        for event in events {
+            match event {
+                GameEvent::RoundStarted { .. } | GameEvent::Countdown { .. } => keyframe = true,
+                GameEvent::GameStarted => {
+                    self.broadcast(ServerMessage::GameStarted);
+                    keyframe = true;
+                }
+                GameEvent::RoundOver { round, winner, standings } => {
+                    self.broadcast(ServerMessage::RoundOver { round, winner, standings });
+                }
+                GameEvent::MatchOver { winner, standings } => {
+                    self.record_difficulty();
+                    let replay_id = self.save_recording();
+                    self.broadcast(ServerMessage::MatchOver { winner, standings, replay_id });
+                    // Save scores once per match
+                    for player in self.engine.state.players.values() {
+                        if let Some(creds) = self.credentials.get(&player.id) {
+                            save_score(&creds.user_id, player.total_score, &creds.access_token);
+                        }
+                    }
+                }
+                // Respawned bodies aren't contiguous with the old ones
+                GameEvent::PlayerRespawned { .. } => keyframe = true,
+                GameEvent::FoodEaten { .. }
+                | GameEvent::FoodSpawned { .. }
+                | GameEvent::PowerUpCollected { .. }
+                | GameEvent::PowerUpSpawned { .. }
+                | GameEvent::PlayerDied { .. } => {}
+            }
+        }
+        keyframe
+    }
+
+    fn start_game(&mut self, ctx: &mut Context<Self>) {
+        if self.engine.state.players.is_empty() {
+            return;
+        }
+
+        self.pending_inputs.clear();
+        let seed = rand::random();
+        if self.replays.is_some() {
+            let roster = self.engine.state.players.values().map(replay::RosterEntry::from).collect();
+            let map = self.engine.state.map.as_deref().cloned();
+            self.recording = Some(Replay::new(seed, self.engine.settings.clone(), map, roster));
+        }
+        let events = self.engine.start_match(seed);
+        if self.handle_events(events) {
+            self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+        }
+
+        // Start game loop ONLY if not already running (prevents speed accumulation!)
+        if self.game_loop.is_none() {
+            self.game_loop = Some(ctx.run_interval(self.engine.settings.speed.tick_interval(), |act, _ctx| {
+                act.tick();
+            }));
+        }
+    }
+
+    /// Hand the finished recording to the replay store; returns its id
+    fn save_recording(&mut self) -> Option<String> {
+        let replay = self.recording.take()?;
+        let store = self.replays.clone()?;
+        let id = replay.id.clone();
+        actix_web::rt::task::spawn_blocking(move || {
+            if let Err(e) = store.save(&replay) {
+                log::error!("Failed to save replay {}: {}", replay.id, e);
+            }
+        });
+        Some(id)
+    }
+
+    fn stop_game_loop(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.game_loop.take() {
+            ctx.cancel_future(handle);
+        }
+    }
+
+    /// Kick every socket and stop the actor; `stopped` then notifies the manager
+    fn close(&mut self, reason: &str, ctx: &mut Context<Self>) {
+        log::info!("Closing room {}: {}", self.room_code, reason);
+        self.broadcast(ServerMessage::Error { message: reason.to_string() });
+        for addr in self.sessions.values() {
+            addr.do_send(CloseSession);
+        }
+        self.sessions.clear();
+        self.stop_game_loop(ctx);
+        ctx.stop();
+    }
+}
+
+impl Actor for GameRoom {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_later(MAX_ROOM_LIFETIME, |act, ctx| {
+            act.close("Room reached its maximum lifetime", ctx);
+        });
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        if let Some(manager) = &self.manager {
+            manager.do_send(RoomClosed { room_id: self.room_id.clone() });
+        }
+    }
+}
+
+impl Handler<Connect> for GameRoom {
+    type Result = ();
+
+    fn handle(&mut self, msg: Connect, _ctx: &mut Self::Context) -> Self::Result {
+        let id = msg.id.clone();
+        let resume_token = Uuid::new_v4().to_string();
+        self.resume_tokens.insert(resume_token.clone(), id.clone());
+        self.sessions.insert(id.clone(), msg.addr);
+        if msg.spectator {
+            self.engine.state.spectators.insert(id.clone(), "Spectator".to_string());
+        }
+        self.send_to(&id.clone(), ServerMessage::Welcome { player_id: id.clone(), resume_token });
+        // Send initial game state so frontend can render lobby
+        self.send_to(&id, ServerMessage::GameState(self.engine.state.view()));
+        self.report_status();
+    }
+}
+
+impl Handler<Disconnect> for GameRoom {
+    type Result = ();
+
+    fn handle(&mut self, msg: Disconnect, ctx: &mut Self::Context) -> Self::Result {
+        let id = self.resumed.remove(&msg.id).unwrap_or(msg.id);
+        // A socket replaced by `Resume` must not evict the player it handed over
+        if self.sessions.get(&id) != Some(&msg.addr) {
+            return;
+        }
+        self.sessions.remove(&id);
+
+        let Some(player) = self.engine.state.players.get_mut(&id) else {
+            // Spectator or never joined: nothing to keep around
+            self.resume_tokens.retain(|_, player_id| *player_id != id);
+            if self.engine.state.spectators.remove(&id).is_some() {
+                self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+            }
+            self.report_status();
+            return;
+        };
+
+        // Keep the seat (and the snake) for a grace window so the player can resume
+        player.connected = false;
+        let player_id = id.clone();
+        let handle = ctx.run_later(self.resume_grace, move |act, ctx| {
+            act.pending_removals.remove(&player_id);
+            act.remove_player(&player_id, ctx);
+        });
+        self.pending_removals.insert(id, handle);
+
+        self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+        self.report_status();
+    }
+}
+
+impl GameRoom {
+    /// Drop a player for good once their resume window has passed
+    fn remove_player(&mut self, id: &str, ctx: &mut Context<Self>) {
+        let id = id.to_string();
+        self.resume_tokens.retain(|_, player_id| *player_id != id);
+        self.credentials.remove(&id);
+        if self.engine.state.remove_player(&id).is_some() {
+            if let Some(recording) = &mut self.recording {
+                recording.record_leave(&id);
+            }
+        }
+
+        // Hand ownership to another human if the owner left
+        if self.owner_id.as_deref() == Some(id.as_str()) {
+            self.owner_id = self.engine.state.players.values()
+                .find(|p| !p.is_bot)
+                .map(|p| p.id.clone());
+        }
+
+        self.broadcast(ServerMessage::PlayerLeft { player_id: id });
+
+        // Reset game to Lobby when all players leave
+        if self.engine.state.players.is_empty() {
+            self.engine.reset();
+            self.recording = None;
+            self.stop_game_loop(ctx); // Allow new game loop to start
+        }
+        
+        self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+        self.report_status();
+    }
+}
+
+impl Handler<ClientAction> for GameRoom {
+    type Result = ();
+
+    fn handle(&mut self, action: ClientAction, ctx: &mut Self::Context) -> Self::Result {
+        // A socket that resumed acts as the player it took over, from its next message on
+        let action = match self.resumed.get(&action.id) {
+            Some(player_id) => ClientAction { id: player_id.clone(), msg: action.msg },
+            None => action,
+        };
+        match action.msg {
+            ClientMessage::Join { name, user_id, access_token } => {
+                if self.engine.settings.bot_arena {
+                    self.send_to(&action.id, ServerMessage::Error {
+                        message: "Only bots play in a bot arena; you can spectate".to_string()
+                    });
+                    return;
+                }
+                if self.engine.state.players.len() >= self.engine.settings.max_players {
+                    self.send_to(&action.id, ServerMessage::Error { 
+                        message: format!("Room is full (max {} players)", self.engine.settings.max_players)
+                    });
+                    return;
+                }
+
+                // Spectators can take a seat between rounds, but never mid-round
+                // (arenas drop newcomers straight into the action)
+                if self.engine.state.phase == GamePhase::Playing && self.engine.settings.mode != GameMode::Arena {
+                    self.send_to(&action.id, ServerMessage::Error { 
+                        message: "Game already in progress".to_string() 
+                    });
+                    return;
+                }
+                self.engine.state.spectators.remove(&action.id);
+
+                let slot = self.engine.state.free_slot();
+                let (pos, dir) = self.engine.state.get_spawn_position(slot);
+
+                let credentials = match self.authenticate(user_id, access_token) {
+                    Ok(credentials) => credentials,
+                    Err(e) => {
+                        self.send_to(&action.id, ServerMessage::AuthFailed {
+                            code: e.code().to_string(),
+                            message: e.to_string(),
+                        });
+                        return;
+                    }
+                };
+                let authenticated = credentials.is_some();
+                match credentials {
+                    Some(credentials) => self.credentials.insert(action.id.clone(), credentials),
+                    None => self.credentials.remove(&action.id),
+                };
+
+                let player = Player {
+                    authenticated,
+                    ..Player::new(action.id.clone(), name.clone(), slot, pos, dir)
+                };
+
+                if let Some(recording) = &mut self.recording {
+                    recording.record_join(&player);
+                }
+                self.engine.add_player(player);
+                self.seed_difficulty(&action.id);
+                if self.owner_id.is_none() {
+                    self.owner_id = Some(action.id.clone());
+                }
+                self.broadcast(ServerMessage::PlayerJoined { 
+                    player_id: action.id, 
+                    name 
+                });
+                self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+            }
+
+            ClientMessage::Resume { token } => {
+                self.resume(&action.id, &token, ctx);
+            }
+
+            ClientMessage::Spectate { name } => {
+                if self.engine.state.players.contains_key(&action.id) {
+                    // Players can only step out while no round is being played
+                    if self.engine.state.phase == GamePhase::Playing {
+                        self.send_to(&action.id, ServerMessage::Error {
+                            message: "Cannot spectate in the middle of a round".to_string()
+                        });
+                        return;
+                    }
+                    self.remove_player(&action.id, ctx);
+                }
+                let name = name.unwrap_or_else(|| "Spectator".to_string());
+                self.engine.state.spectators.insert(action.id.clone(), name);
+                self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+            }
+
+            ClientMessage::Ready => {
+                if let Some(player) = self.engine.state.players.get_mut(&action.id) {
+                    player.ready = true;
+                }
+                self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+            }
+
+            ClientMessage::Direction { direction } => {
+                // Queued for the next tick so every input goes through the engine
+                if self.game_loop.is_some() && self.engine.state.players.contains_key(&action.id) {
+                    self.pending_inputs.push(Input { player_id: action.id, direction });
+                }
+            }
+
+            ClientMessage::StartGame => {
+                // Only start if all players are ready
+                let all_ready = !self.engine.state.players.is_empty() 
+                    && self.engine.state.players.values().all(|p| p.ready);
+                
+                if all_ready && self.engine.state.phase == GamePhase::Lobby {
+                    self.start_game(ctx);
+                }
+            }
+
+            ClientMessage::Restart => {
+                if self.engine.state.phase == GamePhase::MatchOver {
+                    self.engine.state.phase = GamePhase::Lobby;
+                    for player in self.engine.state.players.values_mut() {
+                        player.ready = player.is_bot; // Bots are always ready, or bot arenas could never restart
+                    }
+                    self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+                }
+            }
+
+            ClientMessage::PlayAgain => {
+                if self.engine.state.phase == GamePhase::MatchOver {
+                    // Quick rematch - reset ready states and auto-start if all ready
+                    for player in self.engine.state.players.values_mut() {
+                        player.ready = player.is_bot; // Bots are always ready, or bot arenas could never restart
+                    }
+                    self.engine.state.phase = GamePhase::Lobby;
+                    self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+                }
+            }
+
+            ClientMessage::Resync => {
+                self.send_to(&action.id, ServerMessage::GameState(self.engine.state.view()));
+            }
+        }
+
+        self.report_status();
+    }
+}
+
+impl Handler<SpawnBot> for GameRoom {
+    type Result = ();
+
+    fn handle(&mut self, msg: SpawnBot, _ctx: &mut Self::Context) -> Self::Result {
+        self.spawn_bot(&msg.strategy, msg.name);
+        self.report_status();
+    }
+}
+
+impl GameRoom {
+    /// Reattach the socket `session_id` to the player that owns `token`
+    fn resume(&mut self, session_id: &str, token: &str, ctx: &mut Context<Self>) {
+        let player_id = match self.resume_tokens.get(token) {
+            Some(player_id) if self.engine.state.players.contains_key(player_id) => player_id.clone(),
+            _ => {
+                self.send_to(session_id, ServerMessage::Error {
+                    message: "Session expired, please join again".to_string()
+                });
+                return;
+            }
+        };
+        if player_id == session_id {
+            return;
+        }
+        let Some(addr) = self.sessions.remove(session_id) else {
+            return;
+        };
+        if let Some(handle) = self.pending_removals.remove(&player_id) {
+            ctx.cancel_future(handle);
+        }
+
+        // The fresh socket takes over the player's id; its own token is no longer needed.
+        // Rebinding here, rather than on the socket, means no message it sends goes astray
+        self.resume_tokens.retain(|_, id| id != session_id);
+        self.resumed.retain(|_, id| *id != player_id);
+        self.resumed.insert(session_id.to_string(), player_id.clone());
+        self.engine.state.spectators.remove(session_id);
+        if let Some(old) = self.sessions.insert(player_id.clone(), addr) {
+            old.do_send(CloseSession);
+        }
+
+        if let Some(player) = self.engine.state.players.get_mut(&player_id) {
+            player.connected = true;
+        }
+        log::info!("Player {} resumed in room {}", player_id, self.room_code);
+
+        self.send_to(&player_id, ServerMessage::Welcome {
+            player_id: player_id.clone(),
+            resume_token: token.to_string(),
+        });
+        self.broadcast(ServerMessage::GameState(self.engine.state.view()));
+    }
+}
+
+impl Handler<CloseRoom> for GameRoom {
+    type Result = ();
+
+    fn handle(&mut self, msg: CloseRoom, ctx: &mut Self::Context) -> Self::Result {
+        self.close(&msg.reason, ctx);
+    }
+}
+
+impl Handler<FillWithBots> for GameRoom {
+    type Result = ();
+
+    fn handle(&mut self, msg: FillWithBots, _ctx: &mut Self::Context) -> Self::Result {
+        // Only fill lobbies that still have a human waiting in them
+        let has_human = self.engine.state.players.values().any(|p| !p.is_bot);
+        if self.engine.state.phase != GamePhase::Lobby || !has_human {
+            return;
+        }
+
+        while self.spawn_bot(&msg.strategy, None) {}
+        self.report_status();
+    }
+}
+
+// =============================================================================
+// WEBSOCKET SESSION
+// =============================================================================
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct WsMessage(pub String);
+
+/// Sent by a closing room so the socket shuts down too
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseSession;
+
+pub struct SnakeSession {
+    pub id: String,
+    pub room: Addr<GameRoom>,
+    pub hb: Instant,
+    pub spectator: bool,
+}
+
+impl SnakeSession {
+    pub fn new(room: Addr<GameRoom>, spectator: bool) -> Self {
+        SnakeSession {
+            id: Uuid::new_v4().to_string(),
+            room,
+            hb: Instant::now(),
+            spectator,
+        }
+    }
+
+    fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+                log::warn!("WebSocket client heartbeat failed, disconnecting!");
+                act.room.do_send(Disconnect { id: act.id.clone(), addr: ctx.address() });
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+}
+
+impl Actor for SnakeSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.hb(ctx);
+
+        self.room.do_send(Connect {
+            addr: ctx.address(),
+            id: self.id.clone(),
+            spectator: self.spectator,
+        });
+    }
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        self.room.do_send(Disconnect { id: self.id.clone(), addr: ctx.address() });
+    }
+}
+
+/// Read-only WebSocket feed of public room changes for the room browser
+pub struct LobbySession {
+    pub id: String,
+    pub manager: Addr<RoomManager>,
+    pub hb: Instant,
+}
+
+impl LobbySession {
+    pub fn new(manager: Addr<RoomManager>) -> Self {
+        LobbySession {
+            id: Uuid::new_v4().to_string(),
+            manager,
+            hb: Instant::now(),
+        }
+    }
+
+    fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+                log::warn!("Lobby client heartbeat failed, disconnecting!");
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+}
+
+impl Actor for LobbySession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.hb(ctx);
+
+        self.manager.do_send(SubscribeLobby {
+            id: self.id.clone(),
+            addr: ctx.address().recipient(),
+        });
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        self.manager.do_send(UnsubscribeLobby { id: self.id.clone() });
+    }
+}
+
+impl Handler<WsMessage> for LobbySession {
+    type Result = ();
+
+    fn handle(&mut self, msg: WsMessage, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for LobbySession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => {
+                self.hb = Instant::now();
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Handler<WsMessage> for SnakeSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: WsMessage, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+    }
+}
+
+impl Handler<CloseSession> for SnakeSession {
+    type Result = ();
+
+    fn handle(&mut self, _msg: CloseSession, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseCode::Normal.into()));
+        ctx.stop();
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for SnakeSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => {
+                self.hb = Instant::now();
+            }
+            Ok(ws::Message::Text(text)) => {
+                if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
+                    self.room.do_send(ClientAction {
+                        id: self.id.clone(),
+                        msg: client_msg,
+                    });
+                }
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bots::ADAPTIVE_START_SKILL;
+    use std::pin::Pin;
+    use std::sync::Mutex;
+    use std::task::Poll;
+
+    const SECRET_TOKEN: &str = "eyJhbGciOiJIUzI1NiJ9.secret-access-token";
+    const SECRET_USER: &str = "5f0c6d1e-supabase-user-id";
+
+    fn room_with_authenticated_player() -> GameRoom {
+        let mut room = GameRoom::new("room".to_string(), "ABC123".to_string(), RoomSettings::default(), true);
+        let (pos, dir) = room.engine.state.get_spawn_position(0);
+        room.engine.state.players.insert("p1".to_string(), Player {
+            ready: true,
+            authenticated: true,
+            ..Player::new("p1".to_string(), "Alice".to_string(), 0, pos, dir)
+        });
+        room.credentials.insert("p1".to_string(), Credentials {
+            user_id: SECRET_USER.to_string(),
+            access_token: SECRET_TOKEN.to_string(),
+        });
+        room
+    }
+
+    #[test]
+    fn server_messages_never_serialize_credentials() {
+        let mut room = room_with_authenticated_player();
+        room.engine.state.phase = GamePhase::Playing;
+        let base = DeltaBase::mark(&room.engine.state).unwrap();
+        room.engine.state.tick += 1;
+        for player in room.engine.state.players.values_mut() {
+            player.snake.move_forward();
+        }
+        let delta = base.delta(&room.engine.state, &[]).expect("plain move is expressible as a delta");
+
+        let messages = vec![
+            ServerMessage::GameState(room.engine.state.view()),
+            ServerMessage::Delta(delta),
+            ServerMessage::PlayerJoined { player_id: "p1".to_string(), name: "Alice".to_string() },
+            ServerMessage::RoundOver { round: 1, winner: None, standings: room.engine.state.standings() },
+            ServerMessage::MatchOver { winner: None, standings: room.engine.state.standings(), replay_id: None },
+        ];
+
+        for msg in messages {
+            let json = serde_json::to_string(&msg).unwrap();
+            assert!(!json.contains(SECRET_TOKEN), "access token leaked: {}", json);
+            assert!(!json.contains(SECRET_USER), "user id leaked: {}", json);
+            assert!(!json.contains("access_token"), "credential field serialized: {}", json);
+        }
+    }
+
+    #[test]
+    fn adaptive_rating_follows_the_player_into_their_next_room() {
+        let ratings = Arc::new(DifficultyRatings::default());
+        let adaptive_skill = |room: &GameRoom| room.engine.state.players.values().find_map(|p| p.skill);
+        let mut first = room_with_authenticated_player();
+        first.ratings = ratings.clone();
+        first.seed_difficulty("p1");
+        assert!(first.spawn_bot("Adaptive", None));
+        assert_eq!(adaptive_skill(&first), Some(ADAPTIVE_START_SKILL));
+
+        first.engine.state.players.get_mut("p1").unwrap().round_wins = 1;
+        first.engine.state.phase = GamePhase::MatchOver;
+        first.record_difficulty();
+        let raised = adaptive_skill(&first).unwrap();
+        assert!(raised > ADAPTIVE_START_SKILL);
+
+        // A fresh room (a new /solo game) starts where the last one left off
+        let mut second = room_with_authenticated_player();
+        second.ratings = ratings;
+        assert!(second.spawn_bot("Adaptive", None));
+        second.seed_difficulty("p1");
+        assert_eq!(adaptive_skill(&second), Some(raised));
+    }
+
+    /// Never yields: a client that sends nothing over the wire
+    struct Silent;
+
+    impl Stream for Silent {
+        type Item = Result<actix_web::web::Bytes, actix_web::error::PayloadError>;
+
+        fn poll_next(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Pending
+        }
+    }
+
+    /// A real `SnakeSession` on a fake connection, keeping what the room sends it
+    struct TestSocket {
+        addr: Addr<SnakeSession>,
+        inbox: Arc<Mutex<Vec<ServerMessage>>>,
+    }
+
+    impl TestSocket {
+        fn connect(room: &Addr<GameRoom>, id: &str) -> Self {
+            let session = SnakeSession { id: id.to_string(), room: room.clone(), hb: Instant::now(), spectator: false };
+            let (addr, frames) = ws::WebsocketContext::create_with_addr(session, Silent);
+            let inbox = Arc::new(Mutex::new(Vec::new()));
+            let received = inbox.clone();
+            actix::spawn(async move {
+                let mut frames = Box::pin(frames);
+                let mut buf = Vec::new();
+                while let Some(Ok(bytes)) = std::future::poll_fn(|cx| frames.as_mut().poll_next(cx)).await {
+                    buf.extend_from_slice(&bytes);
+                    // Server frames are unmasked: opcode, then a 7, 16 or 64 bit length
+                    while buf.len() >= 2 {
+                        let (len, start) = match buf[1] & 0x7f {
+                            126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as usize, 4),
+                            127 if buf.len() >= 10 => (u64::from_be_bytes(buf[2..10].try_into().unwrap()) as usize, 10),
+                            126 | 127 => break,
+                            len => (len as usize, 2),
+                        };
+                        if buf.len() < start + len {
+                            break;
+                        }
+                        if buf[0] & 0x0f == 1 {
+                            received.lock().unwrap().push(serde_json::from_slice(&buf[start..start + len]).unwrap());
+                        }
+                        buf.drain(..start + len);
+                    }
+                }
+            });
+            TestSocket { addr, inbox }
+        }
+
+        fn resume_token(&self) -> String {
+            self.inbox.lock().unwrap().iter()
+                .find_map(|msg| match msg {
+                    ServerMessage::Welcome { resume_token, .. } => Some(resume_token.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        }
+
+        fn last_state(&self) -> GameStateView {
+            self.inbox.lock().unwrap().iter().rev()
+                .find_map(|msg| match msg {
+                    ServerMessage::GameState(state) => Some(state.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        }
+    }
+
+    fn act(id: &str, msg: ClientMessage) -> ClientAction {
+        ClientAction { id: id.to_string(), msg }
+    }
+
+    fn join(name: &str) -> ClientMessage {
+        ClientMessage::Join { name: name.to_string(), user_id: None, access_token: None }
+    }
+
+    async fn settle() {
+        actix::clock::sleep(Duration::from_millis(30)).await;
+    }
+
+    #[actix::test]
+    async fn a_resumed_socket_acts_as_its_player_straight_away() {
+        let room = GameRoom::new("room".to_string(), "ABC123".to_string(), RoomSettings::default(), true).start();
+        let first = TestSocket::connect(&room, "s1");
+        room.send(act("s1", join("Alice"))).await.unwrap();
+        first.addr.send(CloseSession).await.unwrap();
+        settle().await;
+
+        // Resume and a move in the same breath, before the socket could hear back
+        let second = TestSocket::connect(&room, "s2");
+        settle().await;
+        room.do_send(act("s2", ClientMessage::Resume { token: first.resume_token() }));
+        room.do_send(act("s2", ClientMessage::Ready));
+        settle().await;
+        let state = second.last_state();
+        assert_eq!(state.players.keys().collect::<Vec<_>>(), ["s1"]);
+        assert!(state.players["s1"].connected && state.players["s1"].ready);
+
+        // Leaving through the new socket is leaving as the player
+        second.addr.send(CloseSession).await.unwrap();
+        let watcher = TestSocket::connect(&room, "s3");
+        settle().await;
+        assert!(!watcher.last_state().players["s1"].connected);
+    }
+
+    #[actix::test]
+    async fn dropped_players_are_removed_when_the_grace_window_ends() {
+        let mut room = GameRoom::new("room".to_string(), "ABC123".to_string(), RoomSettings::default(), true);
+        room.resume_grace = Duration::from_millis(100);
+        let room = room.start();
+        let first = TestSocket::connect(&room, "s1");
+        room.send(act("s1", join("Alice"))).await.unwrap();
+        first.addr.send(CloseSession).await.unwrap();
+        settle().await;
+
+        let second = TestSocket::connect(&room, "s2");
+        settle().await;
+        assert!(!second.last_state().players["s1"].connected);
+
+        actix::clock::sleep(Duration::from_millis(150)).await;
+        room.send(act("s2", ClientMessage::Resume { token: first.resume_token() })).await.unwrap();
+        settle().await;
+        assert!(second.last_state().players.is_empty());
+        assert!(matches!(second.inbox.lock().unwrap().last(), Some(ServerMessage::Error { .. })));
+    }
+
+    #[actix::test]
+    async fn spectators_are_listed_by_name_as_they_come_and_go() {
+        let room = GameRoom::new("room".to_string(), "ABC123".to_string(), RoomSettings::default(), true).start();
+        let player = TestSocket::connect(&room, "player-session");
+        let zed = TestSocket::connect(&room, "zed-session");
+        let _amy = TestSocket::connect(&room, "amy-session");
+        settle().await;
+        room.send(act("zed-session", ClientMessage::Spectate { name: Some("Zed".to_string()) })).await.unwrap();
+        room.send(act("amy-session", ClientMessage::Spectate { name: Some("Amy".to_string()) })).await.unwrap();
+        settle().await;
+
+        let state = player.last_state();
+        assert_eq!(state.spectators, ["Amy", "Zed"]);
+        assert!(!serde_json::to_string(&state).unwrap().contains("-session"));
+
+        zed.addr.send(CloseSession).await.unwrap();
+        settle().await;
+        assert_eq!(player.last_state().spectators, ["Amy"]);
+    }
+
+    /// A room mid-round on the small board, its loop already running at `speed`
+    fn playing_room(speed: GameSpeed) -> Addr<GameRoom> {
+        let settings = RoomSettings { speed, map_size: MapSize::Small, power_ups_enabled: false, ..RoomSettings::default() };
+        let mut room = GameRoom::new("room".to_string(), "ABC123".to_string(), settings, true);
+        let (pos, dir) = room.engine.state.get_spawn_position(0);
+        room.engine.add_player(Player { ready: true, ..Player::new("p1".to_string(), "Alice".to_string(), 0, pos, dir) });
+        let mut ctx = Context::new();
+        room.start_game(&mut ctx);
+        room.engine.state.phase = GamePhase::Playing; // Skip the countdown
+        room.engine.state.countdown = 0;
+        ctx.run(room)
+    }
+
+    #[actix::test]
+    async fn room_settings_set_the_tick_rate_and_the_board_edges() {
+        let (fast, slow) = (playing_room(GameSpeed::Fast), playing_room(GameSpeed::Slow));
+        let (fast_socket, slow_socket) = (TestSocket::connect(&fast, "s1"), TestSocket::connect(&slow, "s2"));
+        actix::clock::sleep(Duration::from_millis(600)).await;
+        fast.send(act("s1", ClientMessage::Spectate { name: None })).await.unwrap();
+        slow.send(act("s2", ClientMessage::Spectate { name: None })).await.unwrap();
+        settle().await;
+        let (fast_state, slow_state) = (fast_socket.last_state(), slow_socket.last_state());
+        assert!(fast_state.tick > slow_state.tick, "fast {} vs slow {}", fast_state.tick, slow_state.tick);
+        assert_eq!((fast_state.grid_width, fast_state.grid_height), MapSize::Small.dimensions());
+
+        // Two cells short of the small board's right edge, which a medium board would still have room past
+        let mut engine = Engine::new(1, RoomSettings { map_size: MapSize::Small, power_ups_enabled: false, ..RoomSettings::default() });
+        let (width, _) = MapSize::Small.dimensions();
+        let (pos, dir) = engine.state.get_spawn_position(0);
+        engine.add_player(Player { ready: true, ..Player::new("p1".to_string(), "Alice".to_string(), 0, pos, dir) });
+        engine.start_match(1);
+        engine.state.phase = GamePhase::Playing;
+        let snake = &mut engine.state.players.get_mut("p1").unwrap().snake;
+        snake.body = (0..3).map(|i| Point { x: width - 2 - i, y: 10 }).collect();
+        snake.direction = Direction::Right;
+        snake.next_direction = Direction::Right;
+        engine.step(&[]);
+        assert!(engine.state.players["p1"].snake.alive);
+        engine.step(&[]);
+        let snake = &engine.state.players["p1"].snake;
+        assert!(!snake.alive);
+        assert_eq!(snake.death.as_ref().map(|d| d.cause), Some(DeathCause::Wall));
+    }
+
+    /// What a delta carries about the board, in a comparable form
+    fn seen(view: &GameStateView) -> String {
+        let mut food = view.food.clone();
+        food.sort_by_key(|p| (p.x, p.y));
+        let mut power_ups: Vec<&str> = view.power_ups.iter().map(|pu| pu.id.as_str()).collect();
+        power_ups.sort();
+        let players: Vec<_> = view.players.values()
+            .map(|p| (
+                &p.snake.body, p.snake.direction, p.snake.alive, p.snake.score, &p.snake.death,
+                p.total_score, p.kills, p.deaths, p.active_power.as_ref().map(|a| &a.power_type), p.skill,
+            ))
+            .collect();
+        format!("{} {:?} {:?} {:?}", view.tick, players, food, power_ups)
+    }
+
+    #[test]
+    fn deltas_rebuild_the_state_and_gaps_ask_for_a_keyframe() {
+        let settings = RoomSettings { mode: GameMode::Arena, max_players: 6, ..RoomSettings::default() };
+        let mut engine = Engine::new(3, settings);
+        for slot in 0..6 {
+            let (pos, dir) = engine.state.get_spawn_position(slot);
+            let strategy = if slot % 2 == 0 { "Hard" } else { "Easy" };
+            engine.add_player(Player::new_bot(format!("bot-{}", slot), format!("Bot {}", slot), strategy.to_string(), slot, pos, dir));
+        }
+        let mut brains = BotBrains::new(Arc::new(BotRegistry::builtin()));
+        let mut rng = rand::rngs::mock::StepRng::new(7, 11);
+        engine.start_match(3);
+
+        let mut client = engine.state.view();
+        let (mut deltas, mut keyframes, mut missed) = (0, 0, None);
+        while engine.state.phase != GamePhase::MatchOver && engine.state.tick < 1500 {
+            let inputs = brains.decide_all(&engine.state, &mut rng);
+            let base = DeltaBase::mark(&engine.state);
+            let events = engine.step(&inputs);
+            match base.and_then(|base| base.delta(&engine.state, &events)) {
+                Some(delta) if deltas == 100 && missed.is_none() => {
+                    missed = Some(delta); // Lost on the way; the next one can't apply
+                    deltas += 1;
+                }
+                Some(delta) => {
+                    if !client.apply(&delta) {
+                        assert!(missed.take().is_some(), "delta at tick {} didn't follow on", delta.tick);
+                        client = engine.state.view(); // Resync
+                        continue;
+                    }
+                    assert_eq!(seen(&client), seen(&engine.state.view()));
+                    deltas += 1;
+                }
+                None => {
+                    client = engine.state.view();
+                    keyframes += 1;
+                }
+            }
+        }
+        assert!(deltas > 500 && keyframes * 3 < deltas, "{} deltas, {} keyframes", deltas, keyframes);
+        let (kills, food): (u32, usize) = (client.players.values().map(|p| p.kills).sum(), client.food.len());
+        assert!(kills > 0 && food > 0);
+    }
+
+    #[actix::test]
+    async fn quick_match_holds_seats_and_overflows_into_a_new_room() {
+        let manager = RoomManager::new(Duration::from_secs(60)).start();
+        let mut codes = Vec::new();
+        for _ in 0..MAX_PLAYERS + 1 {
+            let matched = manager.send(QuickMatch).await.unwrap().unwrap();
+            codes.push((matched.code, matched.created));
+        }
+
+        // Nobody has connected yet, but the first four still share a lobby
+        assert!(codes[0].1);
+        assert!(codes[1..MAX_PLAYERS].iter().all(|(code, created)| *code == codes[0].0 && !created));
+        assert!(codes[MAX_PLAYERS].1);
+        assert_ne!(codes[MAX_PLAYERS].0, codes[0].0);
+    }
+
+    #[actix::test]
+    async fn quick_match_bots_arrive_only_after_the_first_join() {
+        let delay = Duration::from_millis(50);
+        let manager = RoomManager::new(delay).start();
+        let code = manager.send(QuickMatch).await.unwrap().unwrap().code;
+        let room = manager.send(JoinRoom { code: code.clone() }).await.unwrap().unwrap();
+        let players = || async {
+            let rooms = manager.send(ListRooms).await.unwrap();
+            rooms.into_iter().find(|r| r.code == code).unwrap().player_count
+        };
+
+        // A lobby nobody has entered yet stays empty
+        actix::clock::sleep(delay * 3).await;
+        assert_eq!(players().await, 0);
+
+        room.send(ClientAction {
+            id: "p1".to_string(),
+            msg: ClientMessage::Join { name: "Alice".to_string(), user_id: None, access_token: None },
+        }).await.unwrap();
+        actix::clock::sleep(delay / 2).await;
+        assert_eq!(players().await, 1);
+        actix::clock::sleep(delay * 2).await;
+        assert_eq!(players().await, MAX_PLAYERS);
+    }
+
+    #[actix::test]
+    async fn only_idle_rooms_are_reaped_and_counted() {
+        let mut manager = RoomManager::default();
+        let mut ctx = Context::new();
+        let create = || CreateRoom { settings: RoomSettings::default(), is_public: true, map: None, bots: None };
+        let (idle, _) = manager.handle(create(), &mut ctx).unwrap();
+        let (busy, _) = manager.handle(create(), &mut ctx).unwrap();
+        let (closed, _) = manager.handle(create(), &mut ctx).unwrap();
+        let room_id = |manager: &RoomManager, code: &str| manager.room_codes[code].clone();
+
+        let idle_id = room_id(&manager, &idle);
+        manager.rooms.get_mut(&idle_id).unwrap().idle_since = Some(Instant::now() - ROOM_IDLE_GRACE);
+        let busy_id = room_id(&manager, &busy);
+        manager.rooms.get_mut(&busy_id).unwrap().idle_since = None;
+        manager.reap_idle_rooms();
+        assert!(!manager.rooms.contains_key(&idle_id));
+        assert!(manager.rooms.contains_key(&busy_id));
+
+        // Rooms that shut themselves down aren't reaps
+        let closed_id = room_id(&manager, &closed);
+        manager.handle(RoomClosed { room_id: closed_id.clone() }, &mut ctx);
+        manager.handle(RoomClosed { room_id: idle_id }, &mut ctx);
+        assert!(!manager.rooms.contains_key(&closed_id));
+
+        let stats = manager.handle(GetRoomStats, &mut ctx).0;
+        assert_eq!((stats.live, stats.idle, stats.reaped), (1, 0, 1));
+    }
+}
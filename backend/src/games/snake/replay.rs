@@ -0,0 +1,443 @@
+use actix::prelude::*;
+use actix_web_actors::ws;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::store::JsonDirStore;
+use super::{
+    Engine, GameMap, GameState, Input, Player, RoomSettings, ServerMessage,
+    CLIENT_TIMEOUT, HEARTBEAT_INTERVAL,
+};
+
+const CHECKPOINT_INTERVAL: u32 = 200; // Engine snapshots kept while playing back, for cheap seeks
+const MIN_REPLAY_SPEED: f32 = 0.25;
+const MAX_REPLAY_SPEED: f32 = 8.0;
+
+// =============================================================================
+// RECORDING
+// =============================================================================
+
+/// A seat at the table, enough to rebuild the player inside an engine
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RosterEntry {
+    pub player_id: String,
+    pub name: String,
+    pub slot: usize,
+    pub is_bot: bool,
+    #[serde(alias = "difficulty")] // Recordings from before bot strategies
+    pub strategy: Option<String>,
+}
+
+impl From<&Player> for RosterEntry {
+    fn from(player: &Player) -> Self {
+        RosterEntry {
+            player_id: player.id.clone(),
+            name: player.name.clone(),
+            slot: player.slot,
+            is_bot: player.is_bot,
+            strategy: player.strategy.clone(),
+        }
+    }
+}
+
+impl RosterEntry {
+    fn to_player(&self, state: &GameState) -> Player {
+        let (pos, dir) = state.get_spawn_position(self.slot);
+        Player {
+            ready: true,
+            is_bot: self.is_bot,
+            strategy: self.strategy.clone(),
+            ..Player::new(self.player_id.clone(), self.name.clone(), self.slot, pos, dir)
+        }
+    }
+}
+
+/// Everything that changed the roster or steered a snake before one engine step
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReplayStep {
+    pub step: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub joined: Vec<RosterEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub left: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<Input>,
+}
+
+/// A whole match as seed + settings + roster + inputs; the engine recreates the rest
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub id: String,
+    pub recorded_at: String, // ISO 8601
+    pub seed: u64,
+    pub settings: RoomSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<GameMap>, // Kept whole so the replay survives the map changing
+    pub roster: Vec<RosterEntry>,
+    pub total_steps: u32,
+    pub steps: Vec<ReplayStep>, // Only steps where something happened, in order
+}
+
+impl Replay {
+    pub fn new(seed: u64, settings: RoomSettings, map: Option<GameMap>, roster: Vec<RosterEntry>) -> Self {
+        Replay {
+            id: uuid::Uuid::new_v4().to_string(),
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            seed,
+            settings,
+            map,
+            roster,
+            total_steps: 0,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Entry for the step about to be taken, created on first use
+    fn pending(&mut self) -> &mut ReplayStep {
+        let step = self.total_steps;
+        if self.steps.last().is_none_or(|last| last.step != step) {
+            self.steps.push(ReplayStep { step, ..ReplayStep::default() });
+        }
+        self.steps.last_mut().unwrap()
+    }
+
+    pub fn record_join(&mut self, player: &Player) {
+        self.pending().joined.push(RosterEntry::from(player));
+    }
+
+    pub fn record_leave(&mut self, player_id: &str) {
+        self.pending().left.push(player_id.to_string());
+    }
+
+    /// Log the inputs fed to the engine for the next step
+    pub fn record_step(&mut self, inputs: &[Input]) {
+        if !inputs.is_empty() {
+            self.pending().inputs.extend_from_slice(inputs);
+        }
+        self.total_steps += 1;
+    }
+
+    /// Engine positioned right after `start_match`, before step 0
+    fn initial_engine(&self) -> Engine {
+        let mut engine = Engine::new(self.seed, self.settings.clone());
+        engine.set_map(self.map.clone().map(Arc::new));
+        for entry in &self.roster {
+            let player = entry.to_player(&engine.state);
+            engine.state.add_player(player);
+        }
+        engine.start_match(self.seed);
+        engine
+    }
+}
+
+// =============================================================================
+// STORAGE
+// =============================================================================
+
+/// Where finished recordings go; the filesystem store is the default
+pub trait ReplayStore: Send + Sync {
+    fn save(&self, replay: &Replay) -> io::Result<()>;
+    fn load(&self, id: &str) -> io::Result<Option<Replay>>;
+}
+
+/// One JSON file per replay under `dir`
+pub type FileReplayStore = JsonDirStore<Replay>;
+
+impl FileReplayStore {
+    /// Reads REPLAY_DIR, defaulting to `./replays`
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("REPLAY_DIR").unwrap_or_else(|_| "replays".to_string()))
+    }
+}
+
+impl ReplayStore for FileReplayStore {
+    fn save(&self, replay: &Replay) -> io::Result<()> {
+        self.write(&replay.id, replay)
+    }
+
+    fn load(&self, id: &str) -> io::Result<Option<Replay>> {
+        self.read(id)
+    }
+}
+
+// =============================================================================
+// PLAYBACK
+// =============================================================================
+
+/// Re-simulates a replay step by step, with checkpoints so seeking stays cheap
+pub struct Playback {
+    replay: Replay,
+    engine: Engine,
+    step: u32,
+    checkpoints: BTreeMap<u32, Engine>,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        let engine = replay.initial_engine();
+        let mut checkpoints = BTreeMap::new();
+        checkpoints.insert(0, engine.clone());
+        Playback { replay, engine, step: 0, checkpoints }
+    }
+
+    pub fn step(&self) -> u32 {
+        self.step
+    }
+
+    pub fn total_steps(&self) -> u32 {
+        self.replay.total_steps
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.engine.state
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        self.replay.settings.speed.tick_interval()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.step >= self.replay.total_steps
+    }
+
+    /// Apply one recorded step; false once the end is reached
+    pub fn advance(&mut self) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+
+        let empty = ReplayStep::default();
+        let recorded = self.replay.steps
+            .binary_search_by_key(&self.step, |s| s.step)
+            .map(|idx| &self.replay.steps[idx])
+            .unwrap_or(&empty);
+
+        for player_id in &recorded.left {
+            self.engine.state.remove_player(player_id);
+        }
+        for entry in &recorded.joined {
+            let player = entry.to_player(&self.engine.state);
+            self.engine.add_player(player);
+        }
+        self.engine.step(&recorded.inputs);
+        self.step += 1;
+
+        let last_checkpoint = self.checkpoints.keys().next_back().copied().unwrap_or(0);
+        if self.step >= last_checkpoint + CHECKPOINT_INTERVAL {
+            self.checkpoints.insert(self.step, self.engine.clone());
+        }
+        true
+    }
+
+    /// Jump to `step` (clamped to the end) by replaying from the nearest checkpoint
+    pub fn seek(&mut self, step: u32) {
+        let step = step.min(self.replay.total_steps);
+        if step < self.step || step - self.step > CHECKPOINT_INTERVAL {
+            if let Some((&at, engine)) = self.checkpoints.range(..=step).next_back() {
+                self.step = at;
+                self.engine = engine.clone();
+            }
+        }
+        while self.step < step && self.advance() {}
+    }
+}
+
+// =============================================================================
+// REPLAY WEBSOCKET SESSION
+// =============================================================================
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", content = "payload")]
+pub enum ReplayCommand {
+    Play,
+    Pause,
+    Seek { step: u32 },
+    Speed { multiplier: f32 },
+}
+
+/// Streams a recorded match as ordinary `GameState` frames
+pub struct ReplaySession {
+    playback: Playback,
+    playing: bool,
+    speed: f32,
+    timer: Option<SpawnHandle>,
+    hb: Instant,
+}
+
+impl ReplaySession {
+    pub fn new(replay: Replay) -> Self {
+        ReplaySession {
+            playback: Playback::new(replay),
+            playing: false,
+            speed: 1.0,
+            timer: None,
+            hb: Instant::now(),
+        }
+    }
+
+    fn send(&self, msg: ServerMessage, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.text(serde_json::to_string(&msg).unwrap());
+    }
+
+    fn send_frame(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        self.send(ServerMessage::GameState(self.playback.state().view()), ctx);
+    }
+
+    fn send_status(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        self.send(ServerMessage::ReplayStatus {
+            step: self.playback.step(),
+            total_steps: self.playback.total_steps(),
+            playing: self.playing,
+            speed: self.speed,
+        }, ctx);
+    }
+
+    /// (Re)start the frame timer at the current speed, or stop it when paused
+    fn reschedule(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if let Some(handle) = self.timer.take() {
+            ctx.cancel_future(handle);
+        }
+        if !self.playing {
+            return;
+        }
+        let interval = self.playback.tick_interval().div_f32(self.speed);
+        self.timer = Some(ctx.run_interval(interval, |act, ctx| {
+            if act.playback.advance() {
+                act.send_frame(ctx);
+            }
+            if act.playback.is_finished() {
+                act.playing = false;
+                act.reschedule(ctx);
+                act.send_status(ctx);
+            }
+        }));
+    }
+
+    fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+                log::warn!("Replay client heartbeat failed, disconnecting!");
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    fn handle_command(&mut self, cmd: ReplayCommand, ctx: &mut ws::WebsocketContext<Self>) {
+        match cmd {
+            ReplayCommand::Play => {
+                if self.playback.is_finished() {
+                    self.playback.seek(0);
+                }
+                self.playing = true;
+            }
+            ReplayCommand::Pause => self.playing = false,
+            ReplayCommand::Seek { step } => {
+                self.playback.seek(step);
+                self.send_frame(ctx);
+            }
+            ReplayCommand::Speed { multiplier } => {
+                if multiplier.is_finite() {
+                    self.speed = multiplier.clamp(MIN_REPLAY_SPEED, MAX_REPLAY_SPEED);
+                }
+            }
+        }
+        self.reschedule(ctx);
+        self.send_status(ctx);
+    }
+}
+
+impl Actor for ReplaySession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.hb(ctx);
+        self.send_frame(ctx);
+        self.send_status(ctx);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ReplaySession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => {
+                self.hb = Instant::now();
+            }
+            Ok(ws::Message::Text(text)) => {
+                if let Ok(cmd) = serde_json::from_str::<ReplayCommand>(&text) {
+                    self.handle_command(cmd, ctx);
+                }
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::snake::{BotBrains, BotRegistry, GamePhase};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Play a bot match the way GameRoom does, recording it as it goes
+    fn record_bot_match(seed: u64) -> (Replay, Engine) {
+        let mut engine = Engine::new(seed, RoomSettings::default());
+        let roster: Vec<RosterEntry> = (0..3)
+            .map(|slot| RosterEntry {
+                player_id: format!("bot-{}", slot),
+                name: format!("Bot {}", slot),
+                slot,
+                is_bot: true,
+                strategy: Some("Medium".to_string()),
+            })
+            .collect();
+        for entry in &roster {
+            engine.state.add_player(entry.to_player(&engine.state));
+        }
+
+        let mut replay = Replay::new(seed, engine.settings.clone(), None, roster);
+        let mut brains = BotBrains::new(Arc::new(BotRegistry::builtin()));
+        let mut bot_rng = ChaCha8Rng::seed_from_u64(seed);
+        engine.start_match(seed);
+        while engine.state.phase != GamePhase::MatchOver && replay.total_steps < 5_000 {
+            let inputs = brains.decide_all(&engine.state, &mut bot_rng);
+            replay.record_step(&inputs);
+            engine.step(&inputs);
+        }
+        (replay, engine)
+    }
+
+    #[test]
+    fn playback_reproduces_the_recorded_match() {
+        let (replay, live) = record_bot_match(7);
+        let json = serde_json::to_string(&replay).unwrap();
+        let mut playback = Playback::new(serde_json::from_str(&json).unwrap());
+
+        while playback.advance() {}
+        let expected = serde_json::to_string(&live.state.view()).unwrap();
+        assert_eq!(serde_json::to_string(&playback.state().view()).unwrap(), expected);
+
+        // Seeking back and forth lands on the same frames
+        let middle = playback.total_steps() / 2;
+        playback.seek(middle);
+        let frame = serde_json::to_string(&playback.state().view()).unwrap();
+        playback.seek(playback.total_steps());
+        playback.seek(middle);
+        assert_eq!(serde_json::to_string(&playback.state().view()).unwrap(), frame);
+        playback.seek(u32::MAX);
+        assert_eq!(serde_json::to_string(&playback.state().view()).unwrap(), expected);
+    }
+}
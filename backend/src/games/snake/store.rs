@@ -0,0 +1,101 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+// =============================================================================
+// JSON DIRECTORY STORE
+// =============================================================================
+
+/// One JSON file per record under `dir`, named after the record's id. Backs the
+/// filesystem map, bot and replay stores.
+pub struct JsonDirStore<T> {
+    dir: PathBuf,
+    records: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> JsonDirStore<T> {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        JsonDirStore { dir: dir.into(), records: PhantomData }
+    }
+
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        // Ids are UUIDs; anything else could walk out of the directory
+        let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        valid.then(|| self.dir.join(format!("{}.json", id)))
+    }
+
+    pub fn write(&self, id: &str, record: &T) -> io::Result<()> {
+        let path = self.path(id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid id '{}'", id)))?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(path, serde_json::to_vec(record)?)
+    }
+
+    pub fn read(&self, id: &str) -> io::Result<Option<T>> {
+        let Some(path) = self.path(id) else {
+            return Ok(None);
+        };
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Every readable record, in no particular order; unreadable files are skipped
+    pub fn read_all(&self) -> io::Result<Vec<T>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut records = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                match serde_json::from_slice(&std::fs::read(&path)?) {
+                    Ok(record) => records.push(record),
+                    Err(e) => log::warn!("Skipping unreadable {}: {}", path.display(), e),
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    /// False if there was nothing to remove
+    pub fn remove(&self, id: &str) -> io::Result<bool> {
+        let Some(path) = self.path(id) else {
+            return Ok(false);
+        };
+        match std::fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_round_trip_and_ids_stay_inside_the_directory() {
+        let dir = std::env::temp_dir().join(format!("chill_space_store_{}", uuid::Uuid::new_v4()));
+        let store: JsonDirStore<Vec<u32>> = JsonDirStore::new(&dir);
+        assert!(store.read_all().unwrap().is_empty()); // Directory not created yet
+
+        store.write("a-1", &vec![1, 2]).unwrap();
+        assert_eq!(store.read("a-1").unwrap(), Some(vec![1, 2]));
+        assert!(store.write("../escape", &vec![3]).is_err());
+        assert_eq!(store.read("../a-1").unwrap(), None);
+
+        std::fs::write(dir.join("broken.json"), b"{").unwrap();
+        assert_eq!(store.read_all().unwrap(), vec![vec![1, 2]]);
+        assert!(store.remove("a-1").unwrap());
+        assert!(!store.remove("a-1").unwrap());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
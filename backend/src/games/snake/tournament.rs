@@ -0,0 +1,346 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::{
+    BotBrains, BotRegistry, DeathCause, Engine, GameEvent, GameMap, GamePhase,
+    Player, RoomSettings,
+};
+
+// =============================================================================
+// CONFIG
+// =============================================================================
+
+/// A batch of bot-only matches, played straight through the engine with no
+/// room, timers or sockets
+#[derive(Clone)]
+pub struct Tournament {
+    pub registry: Arc<BotRegistry>,
+    pub strategies: Vec<String>,      // Rotated through the seats, match by match
+    pub players: usize,               // Seats per match
+    pub matches: usize,
+    pub seed: u64,                    // Match `i` is played with seed `seed + i`
+    pub maps: Vec<Option<Arc<GameMap>>>, // Cycled; None is the open board
+    pub settings: RoomSettings,
+    pub max_ticks: u64,               // Matches still running after this many ticks count as timeouts
+}
+
+impl Tournament {
+    pub fn new(registry: Arc<BotRegistry>, strategies: Vec<String>) -> Self {
+        Tournament {
+            registry,
+            players: strategies.len().max(2),
+            strategies,
+            matches: 100,
+            seed: 0,
+            maps: vec![None],
+            settings: RoomSettings { power_ups_enabled: false, ..RoomSettings::default() },
+            max_ticks: 5_000,
+        }
+    }
+
+    /// Strategies in each seat of match `index`. Every strategy starts in every seat
+    /// equally often, and each lineup is played on every map before the maps repeat.
+    pub fn lineup(&self, index: usize) -> Vec<String> {
+        let count = self.strategies.len();
+        (0..self.players).map(|seat| self.strategies[(index + seat) % count].clone()).collect()
+    }
+
+    fn map_for(&self, index: usize) -> Option<Arc<GameMap>> {
+        let rotation = index / self.strategies.len().max(1);
+        self.maps.get(rotation % self.maps.len().max(1)).cloned().flatten()
+    }
+
+    /// Play every match on `threads` worker threads. Results come back in match
+    /// order, so the report doesn't depend on how the work was split.
+    pub fn run(&self, threads: usize) -> TournamentReport {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(self.matches));
+        thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= self.matches {
+                        break;
+                    }
+                    let result = self.play(index);
+                    results.lock().unwrap().push(result);
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|result| result.index);
+        TournamentReport::from_results(&results)
+    }
+
+    /// Play match `index` to the end (or to `max_ticks`)
+    pub fn play(&self, index: usize) -> MatchResult {
+        let seed = self.seed + index as u64;
+        let lineup = self.lineup(index);
+        let map = self.map_for(index);
+
+        let settings = RoomSettings { max_players: lineup.len(), ..self.settings.clone() }.normalized();
+        let mut engine = Engine::new(seed, settings);
+        engine.set_map(map.clone());
+        for (slot, strategy) in lineup.iter().enumerate() {
+            let (pos, dir) = engine.state.get_spawn_position(slot);
+            engine.add_player(Player::new_bot(seat_id(slot), format!("{} #{}", strategy, slot + 1), strategy.clone(), slot, pos, dir));
+        }
+
+        let mut seats: Vec<SeatResult> = lineup.iter()
+            .map(|strategy| SeatResult { strategy: strategy.clone(), ..SeatResult::default() })
+            .collect();
+        let mut brains = BotBrains::new(self.registry.clone());
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        engine.start_match(seed);
+
+        while engine.state.phase != GamePhase::MatchOver && engine.state.tick < self.max_ticks {
+            if engine.state.phase == GamePhase::Playing {
+                for (slot, seat) in seats.iter_mut().enumerate() {
+                    if engine.state.players.get(&seat_id(slot)).is_some_and(|p| p.snake.alive) {
+                        seat.alive_ticks += 1;
+                    }
+                }
+            }
+
+            let inputs = brains.decide_all(&engine.state, &mut rng);
+            for event in engine.step(&inputs) {
+                match event {
+                    GameEvent::PlayerDied { player_id, cause, .. } => {
+                        if let Some(seat) = seat_index(&player_id).and_then(|slot| seats.get_mut(slot)) {
+                            seat.deaths.record(cause);
+                        }
+                    }
+                    GameEvent::RoundOver { .. } | GameEvent::MatchOver { .. } => {
+                        for (slot, seat) in seats.iter_mut().enumerate() {
+                            if let Some(player) = engine.state.players.get(&seat_id(slot)) {
+                                seat.rounds += 1;
+                                seat.total_length += player.snake.body.len() as u64;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let finished = engine.state.phase == GamePhase::MatchOver;
+        let mut winner = None;
+        for (slot, seat) in seats.iter_mut().enumerate() {
+            let Some(player) = engine.state.players.get(&seat_id(slot)) else {
+                continue;
+            };
+            seat.score = player.total_score;
+            seat.kills = player.kills;
+            seat.won = finished && engine.state.winner.as_ref() == Some(&player.name);
+            if seat.won {
+                winner = Some(seat.strategy.clone());
+            }
+        }
+
+        MatchResult {
+            index,
+            seed,
+            map_id: map.map(|map| map.id.clone()),
+            ticks: engine.state.tick,
+            finished,
+            winner,
+            seats,
+        }
+    }
+}
+
+fn seat_id(slot: usize) -> String {
+    format!("seat-{}", slot)
+}
+
+fn seat_index(player_id: &str) -> Option<usize> {
+    player_id.strip_prefix("seat-")?.parse().ok()
+}
+
+// =============================================================================
+// RESULTS
+// =============================================================================
+
+/// How often each kind of death happened
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct DeathCounts {
+    pub wall: u32,
+    pub self_collision: u32,
+    pub body: u32,
+    pub head_on: u32,
+}
+
+impl DeathCounts {
+    fn record(&mut self, cause: DeathCause) {
+        match cause {
+            DeathCause::Wall => self.wall += 1,
+            DeathCause::SelfCollision => self.self_collision += 1,
+            DeathCause::Body => self.body += 1,
+            DeathCause::HeadOn => self.head_on += 1,
+        }
+    }
+
+    fn add(&mut self, other: &DeathCounts) {
+        self.wall += other.wall;
+        self.self_collision += other.self_collision;
+        self.body += other.body;
+        self.head_on += other.head_on;
+    }
+
+    pub fn total(&self) -> u32 {
+        self.wall + self.self_collision + self.body + self.head_on
+    }
+}
+
+/// How one seat did in one match
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SeatResult {
+    pub strategy: String,
+    pub won: bool,
+    pub rounds: u32,
+    pub alive_ticks: u64,  // Ticks spent alive while a round was being played
+    pub total_length: u64, // Snake length at the end of each round, summed
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: DeathCounts,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MatchResult {
+    pub index: usize,
+    pub seed: u64,
+    pub map_id: Option<String>,
+    pub ticks: u64,
+    pub finished: bool,          // False when it hit the tick limit
+    pub winner: Option<String>,  // Strategy of the winning seat
+    pub seats: Vec<SeatResult>,
+}
+
+/// Totals for one strategy over every seat it played
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StrategyStats {
+    pub strategy: String,
+    pub appearances: u32, // Seats played; a strategy can fill several seats of one match
+    pub wins: u32,
+    pub win_rate: f64,
+    pub rounds: u32,
+    pub avg_length: f64,         // Per round
+    pub avg_survival_ticks: f64, // Per round
+    pub avg_score: f64,          // Per match
+    pub kills: u32,
+    pub deaths: DeathCounts,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TournamentReport {
+    pub matches: usize,
+    pub timeouts: usize, // Matches cut off by the tick limit, which have no winner
+    pub draws: usize,    // Finished matches nobody won
+    pub strategies: Vec<StrategyStats>,
+}
+
+impl TournamentReport {
+    pub fn from_results(results: &[MatchResult]) -> Self {
+        let mut totals: BTreeMap<&str, (StrategyStats, u64, u64, u64)> = BTreeMap::new(); // stats, length, alive, score
+        for seat in results.iter().flat_map(|result| &result.seats) {
+            let (stats, length, alive, score) = totals.entry(&seat.strategy).or_default();
+            stats.appearances += 1;
+            stats.wins += seat.won as u32;
+            stats.rounds += seat.rounds;
+            stats.kills += seat.kills;
+            stats.deaths.add(&seat.deaths);
+            *length += seat.total_length;
+            *alive += seat.alive_ticks;
+            *score += seat.score as u64;
+        }
+
+        let per = |total: u64, count: u32| if count == 0 { 0.0 } else { total as f64 / count as f64 };
+        let strategies = totals.into_iter()
+            .map(|(strategy, (stats, length, alive, score))| StrategyStats {
+                strategy: strategy.to_string(),
+                win_rate: per(stats.wins as u64, stats.appearances),
+                avg_length: per(length, stats.rounds),
+                avg_survival_ticks: per(alive, stats.rounds),
+                avg_score: per(score, stats.appearances),
+                ..stats
+            })
+            .collect();
+
+        TournamentReport {
+            matches: results.len(),
+            timeouts: results.iter().filter(|result| !result.finished).count(),
+            draws: results.iter().filter(|result| result.finished && result.winner.is_none()).count(),
+            strategies,
+        }
+    }
+
+    /// One row per strategy
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "strategy,appearances,wins,win_rate,rounds,avg_length,avg_survival_ticks,avg_score,kills,\
+             deaths_wall,deaths_self,deaths_body,deaths_head_on\n",
+        );
+        for s in &self.strategies {
+            csv.push_str(&format!(
+                "{},{},{},{:.4},{},{:.2},{:.2},{:.2},{},{},{},{},{}\n",
+                s.strategy, s.appearances, s.wins, s.win_rate, s.rounds, s.avg_length,
+                s.avg_survival_ticks, s.avg_score, s.kills, s.deaths.wall,
+                s.deaths.self_collision, s.deaths.body, s.deaths.head_on,
+            ));
+        }
+        csv
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tournament(strategies: &[&str]) -> Tournament {
+        let mut tournament = Tournament::new(
+            Arc::new(BotRegistry::builtin()),
+            strategies.iter().map(|s| s.to_string()).collect(),
+        );
+        tournament.matches = 4;
+        tournament.max_ticks = 1_000;
+        tournament
+    }
+
+    #[test]
+    fn lineups_rotate_every_strategy_through_every_seat() {
+        let tournament = tournament(&["Easy", "Hard", "Medium"]);
+        assert_eq!(tournament.lineup(0), ["Easy", "Hard", "Medium"]);
+        assert_eq!(tournament.lineup(1), ["Hard", "Medium", "Easy"]);
+        assert_eq!(tournament.lineup(5), ["Medium", "Easy", "Hard"]);
+    }
+
+    #[test]
+    fn reports_are_the_same_however_many_threads_play_them() {
+        let tournament = tournament(&["Medium", "Pathfinder"]);
+        let report = tournament.run(1);
+        assert_eq!(report.matches, 4);
+        assert_eq!(serde_json::to_string(&report).unwrap(), serde_json::to_string(&tournament.run(3)).unwrap());
+
+        let wins: u32 = report.strategies.iter().map(|s| s.wins).sum();
+        assert_eq!(wins as usize, report.matches - report.timeouts - report.draws);
+        for stats in &report.strategies {
+            assert_eq!(stats.appearances, 4);
+            assert!(stats.rounds >= 4 - report.timeouts as u32);
+            assert!(stats.avg_length > 0.0 && stats.avg_survival_ticks > 0.0);
+        }
+
+        // Every classic round ends with at most one snake alive
+        let deaths: u32 = report.strategies.iter().map(|s| s.deaths.total()).sum();
+        assert!(deaths as usize >= report.matches - report.timeouts);
+    }
+}
@@ -0,0 +1,6 @@
+//! Chill Space backend: the HTTP/WebSocket server in `main.rs` and the
+//! tools in `src/bin` share these modules.
+
+pub mod auth;
+pub mod code_runner;
+pub mod games;
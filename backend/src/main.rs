@@ -3,16 +3,25 @@ use actix_web::{web, App, HttpServer, HttpRequest, HttpResponse, middleware};
 use actix_web_actors::ws;
 use actix_cors::Cors;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 
-mod code_runner;
-mod games;
+use chill_space_backend::{auth, code_runner, games};
 
-use games::snake::{GameRoom, SnakeSession, RoomManager, CreateRoom, JoinRoom, QuickMatch, RoomSettings};
+use auth::{AdminUser, AuthUser, Authenticator};
+use code_runner::{BotPermit, BotProcessLimits};
+use games::snake::{SnakeSession, LobbySession, RoomManager, CreateRoom, JoinRoom, QuickMatch, ListRooms, RoomInfo, RoomSettings, GetRoomStats};
+use games::snake::{BotRegistry, BotSource, BotSourceStore, FileBotSourceStore, SpawnBot};
+use games::snake::{FileMapStore, FileReplayStore, GameMap, GameMode, MapCatalog, MapStore, Replay, ReplaySession, ReplayStore};
+use games::snake::{parse_actions, Action, EnvConfig, EnvError, EnvLimit, GymEnvs, GymSession, Policy, SnakeEnv, StepResult, ACTIONS, CHANNELS};
 
 // =============================================================================
 // TYPES
 // =============================================================================
 
+const MAX_MAP_UPLOAD_BYTES: usize = 256 * 1024; // JSON maps list every wall cell
+const MAX_BOT_UPLOAD_BYTES: usize = 128 * 1024; // Room for the code runner's 50K characters, JSON-escaped
+
 #[derive(Deserialize)]
 pub struct CodeRequest {
     pub language: String,
@@ -100,13 +109,9 @@ async fn run_code(req: web::Json<CodeRequest>) -> HttpResponse {
     }
 }
 
-async fn snake_ws(
-    req: HttpRequest,
-    stream: web::Payload,
-    room: web::Data<actix::Addr<GameRoom>>,
-) -> Result<HttpResponse, actix_web::Error> {
-    let session = SnakeSession::new(room.get_ref().clone());
-    ws::start(session, &req, stream)
+#[derive(Deserialize)]
+pub struct SnakeWsQuery {
+    pub role: Option<String>, // "spectator" to watch without playing
 }
 
 // Room-based WebSocket connection
@@ -114,6 +119,7 @@ async fn snake_room_ws(
     req: HttpRequest,
     stream: web::Payload,
     path: web::Path<String>,
+    query: web::Query<SnakeWsQuery>,
     room_manager: web::Data<actix::Addr<RoomManager>>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let code = path.into_inner().to_uppercase();
@@ -123,7 +129,8 @@ async fn snake_room_ws(
     
     match room_result {
         Ok(Some(room_addr)) => {
-            let session = SnakeSession::new(room_addr);
+            let spectator = query.role.as_deref() == Some("spectator");
+            let session = SnakeSession::new(room_addr, spectator);
             ws::start(session, &req, stream)
         }
         _ => {
@@ -149,15 +156,34 @@ pub struct CreateRoomResponse {
 
 async fn create_room(
     body: web::Json<CreateRoomRequest>,
+    user: Option<AuthUser>,
     room_manager: web::Data<actix::Addr<RoomManager>>,
+    maps: web::Data<MapCatalog>,
 ) -> HttpResponse {
-    let settings = body.settings.clone().unwrap_or_default();
+    let mut settings = body.settings.clone().unwrap_or_default();
+    settings.bot_arena = false; // Those come from `create_bot_arena`, with their bots seated
     let is_public = body.is_public.unwrap_or(false);
+
+    let map = match settings.map_id.clone() {
+        Some(map_id) => match lookup_map(maps, map_id.clone()).await {
+            Ok(Some(map)) => Some(map),
+            Ok(None) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("Unknown map '{}'", map_id)
+                }));
+            }
+            Err(response) => return response,
+        },
+        None => None,
+    };
     
-    let result = room_manager.send(CreateRoom { settings, is_public }).await;
+    let result = room_manager.send(CreateRoom { settings, is_public, map, bots: None }).await;
     
     match result {
         Ok(Some((code, _))) => {
+            if let Some(user) = user {
+                log::info!("Room {} created by user {}", code, user.user_id);
+            }
             HttpResponse::Ok().json(CreateRoomResponse {
                 code,
                 message: "Room created successfully".to_string(),
@@ -171,9 +197,53 @@ async fn create_room(
     }
 }
 
+#[derive(Serialize)]
+pub struct RoomListResponse {
+    pub rooms: Vec<RoomInfo>,
+}
+
+async fn list_rooms(
+    room_manager: web::Data<actix::Addr<RoomManager>>,
+) -> HttpResponse {
+    match room_manager.send(ListRooms).await {
+        Ok(rooms) => HttpResponse::Ok().json(RoomListResponse { rooms }),
+        Err(_) => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to list rooms"
+            }))
+        }
+    }
+}
+
+// Live/idle/reaped room counts for operators
+async fn room_stats(
+    _admin: AdminUser,
+    room_manager: web::Data<actix::Addr<RoomManager>>,
+) -> HttpResponse {
+    match room_manager.send(GetRoomStats).await {
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(_) => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to read room stats"
+            }))
+        }
+    }
+}
+
+// Live room-browser feed
+async fn rooms_ws(
+    req: HttpRequest,
+    stream: web::Payload,
+    room_manager: web::Data<actix::Addr<RoomManager>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let session = LobbySession::new(room_manager.get_ref().clone());
+    ws::start(session, &req, stream)
+}
+
 #[derive(Serialize)]
 pub struct QuickMatchResponse {
     pub code: String,
+    pub created: bool, // true if a new room was started, false if an existing one was joined
     pub message: String,
 }
 
@@ -183,26 +253,17 @@ async fn quick_match(
     let result = room_manager.send(QuickMatch).await;
     
     match result {
-        Ok(Some(_)) => {
-            // For quick match, we create a new room
-            let create_result = room_manager.send(CreateRoom {
-                settings: RoomSettings::default(),
-                is_public: true,
-            }).await;
-            
-            match create_result {
-                Ok(Some((code, _))) => {
-                    HttpResponse::Ok().json(QuickMatchResponse {
-                        code,
-                        message: "Joined quick match".to_string(),
-                    })
-                }
-                _ => {
-                    HttpResponse::InternalServerError().json(serde_json::json!({
-                        "error": "Failed to find match"
-                    }))
-                }
-            }
+        Ok(Some(matched)) => {
+            let message = if matched.created {
+                "Started a new quick match room"
+            } else {
+                "Joined an existing quick match room"
+            };
+            HttpResponse::Ok().json(QuickMatchResponse {
+                code: matched.code,
+                created: matched.created,
+                message: message.to_string(),
+            })
         }
         _ => {
             HttpResponse::InternalServerError().json(serde_json::json!({
@@ -214,8 +275,11 @@ async fn quick_match(
 
 #[derive(Deserialize)]
 pub struct SoloGameRequest {
-    pub difficulty: String,  // "Easy", "Medium", or "Hard"
-    pub num_bots: u8,        // 1-3
+    #[serde(alias = "strategy")]
+    pub difficulty: String,  // Any registered bot strategy: "Easy", "Medium", "Hard", "Pathfinder", ...
+    pub num_bots: u8,        // 1-3 (1-63 in an arena)
+    #[serde(default)]
+    pub mode: GameMode,
 }
 
 #[derive(Serialize)]
@@ -227,39 +291,45 @@ pub struct SoloGameResponse {
 async fn create_solo_game(
     body: web::Json<SoloGameRequest>,
     room_manager: web::Data<actix::Addr<RoomManager>>,
+    bots: web::Data<BotRegistry>,
 ) -> HttpResponse {
-    use crate::games::snake::BotDifficulty;
-    
-    // Validate num_bots
-    if body.num_bots < 1 || body.num_bots > 3 {
+    // Validate num_bots (one seat is kept for the player)
+    let max_bots = body.mode.max_players() - 1;
+    if body.num_bots < 1 || body.num_bots as usize > max_bots {
         return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "num_bots must be between 1 and 3"
+            "error": format!("num_bots must be between 1 and {}", max_bots)
         }));
     }
     
-    // Parse difficulty
-    let difficulty = match body.difficulty.as_str() {
-        "Easy" => BotDifficulty::Easy,
-        "Medium" => BotDifficulty::Medium,
-        "Hard" => BotDifficulty::Hard,
-        _ => {
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Invalid difficulty. Must be 'Easy', 'Medium', or 'Hard'"
-            }));
-        }
+    // Any registered strategy, matched case-insensitively
+    let Some(strategy) = bots.resolve(&body.difficulty).map(str::to_string) else {
+        let known: Vec<String> = bots.list().into_iter().map(|info| info.name).collect();
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Unknown bot strategy. Must be one of: {}", known.join(", "))
+        }));
     };
     
     // Create a room
+    let settings = match body.mode {
+        GameMode::Classic => RoomSettings::default(),
+        GameMode::Arena => RoomSettings {
+            max_players: body.num_bots as usize + 1,
+            mode: GameMode::Arena,
+            ..RoomSettings::default()
+        },
+    };
     let create_result = room_manager.send(CreateRoom {
-        settings: RoomSettings::default(),
+        settings,
         is_public: false,
+        map: None,
+        bots: None,
     }).await;
     
     match create_result {
         Ok(Some((code, room_addr))) => {
             // Spawn bots
             for _ in 0..body.num_bots {
-                room_addr.do_send(crate::games::snake::SpawnBot { difficulty });
+                room_addr.do_send(SpawnBot { strategy: strategy.clone(), name: None });
             }
             
             HttpResponse::Ok().json(SoloGameResponse {
@@ -275,6 +345,510 @@ async fn create_solo_game(
     }
 }
 
+async fn load_replay(store: web::Data<dyn ReplayStore>, id: String) -> Result<Replay, HttpResponse> {
+    let store = store.into_inner();
+    match web::block(move || store.load(&id)).await {
+        Ok(Ok(Some(replay))) => Ok(replay),
+        Ok(Ok(None)) => Err(HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Replay not found"
+        }))),
+        Ok(Err(e)) => {
+            log::error!("Failed to load replay: {}", e);
+            Err(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to load replay"
+            })))
+        }
+        Err(_) => Err(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load replay"
+        }))),
+    }
+}
+
+async fn list_bots(bots: web::Data<BotRegistry>) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "strategies": bots.list() }))
+}
+
+// Built-in or uploaded map; the store may touch disk, so it runs off the event loop
+async fn lookup_map(maps: web::Data<MapCatalog>, id: String) -> Result<Option<Arc<GameMap>>, HttpResponse> {
+    let maps = maps.into_inner();
+    match web::block(move || maps.get(&id)).await {
+        Ok(Ok(map)) => Ok(map),
+        Ok(Err(e)) => {
+            log::error!("Failed to load map: {}", e);
+            Err(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to load map"
+            })))
+        }
+        Err(_) => Err(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load map"
+        }))),
+    }
+}
+
+async fn list_maps(maps: web::Data<MapCatalog>) -> HttpResponse {
+    let maps = maps.into_inner();
+    match web::block(move || maps.list()).await {
+        Ok(Ok(maps)) => HttpResponse::Ok().json(serde_json::json!({ "maps": maps })),
+        Ok(Err(e)) => {
+            log::error!("Failed to list maps: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to list maps"
+            }))
+        }
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to list maps"
+        })),
+    }
+}
+
+async fn get_map(path: web::Path<String>, maps: web::Data<MapCatalog>) -> HttpResponse {
+    match lookup_map(maps, path.into_inner()).await {
+        Ok(Some(map)) => HttpResponse::Ok().json(map.as_ref()),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Map not found"
+        })),
+        Err(response) => response,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UploadMapRequest {
+    pub name: Option<String>, // Overrides the name inside `source`
+    pub source: String,       // ASCII layout or map JSON, as described in MAPS.md
+}
+
+// Validate and store a user-made map under a fresh id
+async fn upload_map(
+    body: web::Json<UploadMapRequest>,
+    user: AuthUser,
+    store: web::Data<dyn MapStore>,
+) -> HttpResponse {
+    let id = uuid::Uuid::new_v4().to_string();
+    let parsed = GameMap::parse(&id, &body.source).and_then(|mut map| {
+        if let Some(name) = &body.name {
+            map.name = name.trim().to_string();
+            map.validate()?;
+        }
+        Ok(map)
+    });
+    let mut map = match parsed {
+        Ok(map) => map,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e.to_string()
+            }));
+        }
+    };
+    map.author = Some(user.user_id);
+
+    let info = map.info(false);
+    let store = store.into_inner();
+    match web::block(move || store.save(&map)).await {
+        Ok(Ok(())) => {
+            log::info!("Map '{}' uploaded as {}", info.name, info.id);
+            HttpResponse::Ok().json(info)
+        }
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to save map"
+        })),
+    }
+}
+
+// Only the signed-in uploader can delete a map; built-ins stay put
+async fn delete_map(
+    path: web::Path<String>,
+    user: AuthUser,
+    maps: web::Data<MapCatalog>,
+    store: web::Data<dyn MapStore>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if maps.is_builtin(&id) {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "Built-in maps cannot be deleted"
+        }));
+    }
+    let map = match lookup_map(maps, id.clone()).await {
+        Ok(Some(map)) => map,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "Map not found"
+            }));
+        }
+        Err(response) => return response,
+    };
+    if map.author.as_deref() != Some(user.user_id.as_str()) {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "Only the author can delete this map"
+        }));
+    }
+
+    let store = store.into_inner();
+    match web::block(move || store.delete(&id)).await {
+        Ok(Ok(_)) => HttpResponse::NoContent().finish(),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete map"
+        })),
+    }
+}
+
+// Submitted program by id; the store may touch disk, so it runs off the event loop
+async fn lookup_code_bot(store: web::Data<dyn BotSourceStore>, id: String) -> Result<Option<BotSource>, HttpResponse> {
+    let store = store.into_inner();
+    match web::block(move || store.load(&id)).await {
+        Ok(Ok(source)) => Ok(source),
+        Ok(Err(e)) => {
+            log::error!("Failed to load code bot: {}", e);
+            Err(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to load code bot"
+            })))
+        }
+        Err(_) => Err(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load code bot"
+        }))),
+    }
+}
+
+async fn list_code_bots(store: web::Data<dyn BotSourceStore>) -> HttpResponse {
+    let store = store.into_inner();
+    match web::block(move || store.list()).await {
+        Ok(Ok(sources)) => {
+            let bots: Vec<_> = sources.iter().map(BotSource::info).collect();
+            HttpResponse::Ok().json(serde_json::json!({ "bots": bots }))
+        }
+        Ok(Err(e)) => {
+            log::error!("Failed to list code bots: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to list code bots"
+            }))
+        }
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to list code bots"
+        })),
+    }
+}
+
+// Anyone can see a bot; only its author gets the code back
+async fn get_code_bot(
+    path: web::Path<String>,
+    user: Option<AuthUser>,
+    store: web::Data<dyn BotSourceStore>,
+) -> HttpResponse {
+    match lookup_code_bot(store, path.into_inner()).await {
+        Ok(Some(source)) if source.author.is_some() && source.author == user.map(|user| user.user_id) => {
+            HttpResponse::Ok().json(source)
+        }
+        Ok(Some(source)) => HttpResponse::Ok().json(source.info()),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Code bot not found"
+        })),
+        Err(response) => response,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UploadCodeBotRequest {
+    pub name: String,
+    pub language: String, // One of the code runner's languages
+    pub code: String,     // Reads a JSON line per tick on stdin, prints a direction per line
+}
+
+// Check and store a signed-in user's bot program under a fresh id
+async fn upload_code_bot(
+    body: web::Json<UploadCodeBotRequest>,
+    user: AuthUser,
+    store: web::Data<dyn BotSourceStore>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    let source = BotSource {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: body.name.trim().to_string(),
+        language: body.language,
+        code: body.code,
+        author: Some(user.user_id),
+    };
+    if let Err(e) = source.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e
+        }));
+    }
+
+    let info = source.info();
+    let store = store.into_inner();
+    match web::block(move || store.save(&source)).await {
+        Ok(Ok(())) => {
+            log::info!("Code bot '{}' uploaded as {}", info.name, info.id);
+            HttpResponse::Ok().json(info)
+        }
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to save code bot"
+        })),
+    }
+}
+
+async fn delete_code_bot(
+    path: web::Path<String>,
+    user: AuthUser,
+    store: web::Data<dyn BotSourceStore>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let source = match lookup_code_bot(store.clone(), id.clone()).await {
+        Ok(Some(source)) => source,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({
+                "error": "Code bot not found"
+            }));
+        }
+        Err(response) => return response,
+    };
+    if source.author.as_deref() != Some(user.user_id.as_str()) {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "Only the author can delete this bot"
+        }));
+    }
+
+    let store = store.into_inner();
+    match web::block(move || store.delete(&id)).await {
+        Ok(Ok(_)) => HttpResponse::NoContent().finish(),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete code bot"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BotArenaRequest {
+    pub bots: Vec<String>, // Built-in strategy names or uploaded bot ids, one per seat
+    pub settings: Option<RoomSettings>,
+    pub is_public: Option<bool>, // Listed for spectators; defaults to true
+}
+
+// A room where only bots play: built-in strategies and uploaded programs.
+// People join it as spectators, and any of them can start the match.
+async fn create_bot_arena(
+    body: web::Json<BotArenaRequest>,
+    user: AuthUser,
+    room_manager: web::Data<actix::Addr<RoomManager>>,
+    bots: web::Data<BotRegistry>,
+    code_bots: web::Data<dyn BotSourceStore>,
+    maps: web::Data<MapCatalog>,
+    limits: web::Data<BotProcessLimits>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    let mut settings = body.settings.unwrap_or_default();
+    let max_bots = settings.mode.max_players();
+    if body.bots.len() < 2 || body.bots.len() > max_bots {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("A bot arena needs 2 to {} bots", max_bots)
+        }));
+    }
+
+    // Uploaded programs only exist in this room's registry, and run as the creator's.
+    // Their process slots are taken now, so a busy server turns the arena away
+    // rather than seating bots that never start.
+    let limits = limits.into_inner();
+    let mut programs: HashMap<String, (BotSource, Vec<BotPermit>)> = HashMap::new();
+    let mut seats = Vec::new();
+    for entry in &body.bots {
+        if let Some(strategy) = bots.resolve(entry) {
+            seats.push((strategy.to_string(), None));
+            continue;
+        }
+        match lookup_code_bot(code_bots.clone(), entry.clone()).await {
+            Ok(Some(source)) => {
+                let permit = match limits.acquire(&user.user_id) {
+                    Ok(permit) => permit,
+                    Err(e) => return HttpResponse::TooManyRequests().json(serde_json::json!({ "error": e })),
+                };
+                seats.push((source.strategy(), Some(source.name.clone())));
+                programs.entry(source.strategy()).or_insert_with(|| (source, Vec::new())).1.push(permit);
+            }
+            Ok(None) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("'{}' is neither a bot strategy nor an uploaded bot", entry)
+                }));
+            }
+            Err(response) => return response,
+        }
+    }
+
+    let map = match settings.map_id.clone() {
+        Some(map_id) => match lookup_map(maps, map_id.clone()).await {
+            Ok(Some(map)) => Some(map),
+            Ok(None) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("Unknown map '{}'", map_id)
+                }));
+            }
+            Err(response) => return response,
+        },
+        None => None,
+    };
+
+    let mut registry = BotRegistry::clone(&bots);
+    for (source, permits) in programs.into_values() {
+        registry.register_code_bot(&source, permits);
+    }
+    settings.bot_arena = true;
+    settings.max_players = seats.len();
+    let result = room_manager.send(CreateRoom {
+        settings,
+        is_public: body.is_public.unwrap_or(true),
+        map,
+        bots: Some(Arc::new(registry)),
+    }).await;
+
+    match result {
+        Ok(Some((code, room_addr))) => {
+            for (strategy, name) in seats {
+                room_addr.do_send(SpawnBot { strategy, name });
+            }
+            HttpResponse::Ok().json(CreateRoomResponse {
+                code,
+                message: "Bot arena created; join as a spectator to start it".to_string(),
+            })
+        }
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to create bot arena"
+        })),
+    }
+}
+
+// Download a recorded match
+async fn get_replay(
+    path: web::Path<String>,
+    store: web::Data<dyn ReplayStore>,
+) -> HttpResponse {
+    match load_replay(store, path.into_inner()).await {
+        Ok(replay) => HttpResponse::Ok().json(replay),
+        Err(response) => response,
+    }
+}
+
+// Stream a recorded match as GameState frames (Play/Pause/Seek/Speed)
+async fn replay_ws(
+    req: HttpRequest,
+    stream: web::Payload,
+    path: web::Path<String>,
+    store: web::Data<dyn ReplayStore>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match load_replay(store, path.into_inner()).await {
+        Ok(replay) => ws::start(ReplaySession::new(replay), &req, stream),
+        Err(response) => Ok(response),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GymResetRequest {
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub config: EnvConfig,
+}
+
+#[derive(Deserialize)]
+pub struct GymStepRequest {
+    pub actions: Vec<Option<Action>>, // One per agent: a direction, an index into `actions`, or null
+}
+
+// What observations and actions look like, and the config reset starts from
+async fn gym_spec() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "channels": CHANNELS,
+        "actions": ACTIONS,
+        "default_config": EnvConfig::default(),
+    }))
+}
+
+// 503 when every slot is taken, 429 when the caller has their share open
+fn env_limit_response(limit: EnvLimit) -> HttpResponse {
+    let mut response = match limit {
+        EnvLimit::Full => HttpResponse::ServiceUnavailable(),
+        EnvLimit::PerUser => HttpResponse::TooManyRequests(),
+    };
+    response.json(serde_json::json!({ "error": limit.to_string() }))
+}
+
+async fn open_gym_env(user: AuthUser, envs: web::Data<GymEnvs>) -> HttpResponse {
+    match envs.open(&user.user_id) {
+        Ok(id) => HttpResponse::Ok().json(serde_json::json!({ "id": id })),
+        Err(limit) => env_limit_response(limit),
+    }
+}
+
+// Opponents can think for a while, so environments run off the event loop.
+// Only the user who opened an environment can use it.
+async fn with_gym_env(
+    user: AuthUser,
+    envs: web::Data<GymEnvs>,
+    id: String,
+    f: impl FnOnce(&mut SnakeEnv) -> Result<StepResult, EnvError> + Send + 'static,
+) -> HttpResponse {
+    let Some(env) = envs.get(&id, &user.user_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Environment not found"
+        }));
+    };
+    match web::block(move || f(&mut env.lock().unwrap())).await {
+        Ok(Ok(result)) => HttpResponse::Ok().json(result),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e.to_string()
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to run environment"
+        })),
+    }
+}
+
+async fn reset_gym_env(
+    user: AuthUser,
+    path: web::Path<String>,
+    body: web::Json<GymResetRequest>,
+    envs: web::Data<GymEnvs>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    with_gym_env(user, envs, path.into_inner(), move |env| env.reset(body.seed, body.config)).await
+}
+
+async fn step_gym_env(
+    user: AuthUser,
+    path: web::Path<String>,
+    body: web::Json<GymStepRequest>,
+    envs: web::Data<GymEnvs>,
+) -> HttpResponse {
+    let actions = match parse_actions(&body.actions) {
+        Ok(actions) => actions,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e.to_string()
+            }));
+        }
+    };
+    with_gym_env(user, envs, path.into_inner(), move |env| env.step(&actions)).await
+}
+
+async fn close_gym_env(user: AuthUser, path: web::Path<String>, envs: web::Data<GymEnvs>) -> HttpResponse {
+    if envs.close(&path.into_inner(), &user.user_id) {
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Environment not found"
+        }))
+    }
+}
+
+// Reset/Step over a socket, with an environment of its own
+async fn gym_ws(
+    req: HttpRequest,
+    stream: web::Payload,
+    user: AuthUser,
+    envs: web::Data<GymEnvs>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match envs.into_inner().lease(&user.user_id) {
+        Ok(lease) => ws::start(GymSession::new(lease), &req, stream),
+        Err(limit) => Ok(env_limit_response(limit)),
+    }
+}
+
 // =============================================================================
 // MAIN
 // =============================================================================
@@ -294,11 +868,53 @@ async fn main() -> std::io::Result<()> {
     
     log::info!("🚀 Chill Space Backend v{}", env!("CARGO_PKG_VERSION"));
     log::info!("🔒 Security: Timeout=10s, MaxCode=50KB, MaxOutput=100KB");
-    log::info!("🎮 Games: Snake Battle (up to 4 players)");
+    log::info!(
+        "🎮 Games: Snake Battle (up to {} players, {} in arenas)",
+        GameMode::Classic.max_players(),
+        GameMode::Arena.max_players()
+    );
     log::info!("🌐 Starting server on {}:{}", host, port);
     
     // Start the Room Manager
-    let room_manager = RoomManager::default().start();
+    let bot_fill_delay = std::env::var("QUICK_MATCH_BOT_FILL_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .map(std::time::Duration::from_secs);
+    let mut room_manager = match bot_fill_delay {
+        Some(delay) => RoomManager::new(delay),
+        None => RoomManager::default(),
+    };
+
+    let authenticator = Arc::new(Authenticator::from_env());
+    if authenticator.is_enabled() {
+        log::info!("🔐 Auth: Supabase JWT verification enabled");
+    } else {
+        log::warn!("🔐 Auth: no SUPABASE_JWT_SECRET or SUPABASE_JWKS_FILE, everyone plays as a guest");
+    }
+    room_manager.auth = authenticator.clone();
+
+    let replay_store: Arc<dyn ReplayStore> = Arc::new(FileReplayStore::from_env());
+    room_manager.replays = Some(replay_store.clone());
+    let policy_dir = std::env::var("POLICY_DIR").unwrap_or_else(|_| "policies".to_string());
+    match Policy::load_dir(std::path::Path::new(&policy_dir)) {
+        Ok(policies) if !policies.is_empty() => {
+            let mut registry = BotRegistry::clone(&room_manager.bots);
+            for policy in policies {
+                log::info!("🧠 Bots: loaded trained policy '{}'", policy.strategy());
+                registry.register_policy(Arc::new(policy));
+            }
+            room_manager.bots = Arc::new(registry);
+        }
+        Ok(_) => {}
+        Err(e) => log::error!("Failed to read policies from {}: {}", policy_dir, e),
+    }
+    let bots = room_manager.bots.clone();
+    let map_store: Arc<dyn MapStore> = Arc::new(FileMapStore::from_env());
+    let maps = Arc::new(MapCatalog::with_store(map_store.clone()));
+    let code_bots: Arc<dyn BotSourceStore> = Arc::new(FileBotSourceStore::from_env());
+    let bot_limits = Arc::new(BotProcessLimits::from_env());
+    let gym = Arc::new(GymEnvs::new(bots.clone(), maps.clone()));
+    let room_manager = room_manager.start();
     
     HttpServer::new(move || {
         // CORS configuration for frontend
@@ -308,15 +924,24 @@ async fn main() -> std::io::Result<()> {
             .allowed_origin(&frontend_url)
             .allowed_origin("http://localhost:3000") // Always allow local development
             .allowed_origin("http://127.0.0.1:3000")
-            .allowed_methods(vec!["GET", "POST", "OPTIONS"])
+            .allowed_methods(vec!["GET", "POST", "DELETE", "OPTIONS"])
             .allowed_headers(vec![
                 actix_web::http::header::CONTENT_TYPE,
                 actix_web::http::header::ACCEPT,
+                actix_web::http::header::AUTHORIZATION,
             ])
             .max_age(3600);
         
         App::new()
             .app_data(web::Data::new(room_manager.clone()))
+            .app_data(web::Data::from(authenticator.clone()))
+            .app_data(web::Data::from(replay_store.clone()))
+            .app_data(web::Data::from(maps.clone()))
+            .app_data(web::Data::from(map_store.clone()))
+            .app_data(web::Data::from(bots.clone()))
+            .app_data(web::Data::from(code_bots.clone()))
+            .app_data(web::Data::from(bot_limits.clone()))
+            .app_data(web::Data::from(gym.clone()))
             .wrap(cors)
             .wrap(middleware::Logger::default())
             .wrap(middleware::Compress::default())
@@ -325,11 +950,44 @@ async fn main() -> std::io::Result<()> {
             }))
             .route("/api/v1/health", web::get().to(health))
             .route("/api/v1/code/run", web::post().to(run_code))
-            // New room routes
-            .route("/api/v1/games/snake/rooms", web::post().to(create_room))
-            .route("/api/v1/games/snake/quick-match", web::post().to(quick_match))
-            .route("/api/v1/games/snake/solo", web::post().to(create_solo_game))
-            .route("/api/v1/games/snake/ws/{code}", web::get().to(snake_room_ws))
+            // Room routes; a Bearer token, when sent, must be valid
+            .service(
+                web::scope("/api/v1/games/snake")
+                    .wrap(middleware::from_fn(auth::identify))
+                    .route("/rooms", web::post().to(create_room))
+                    .route("/rooms", web::get().to(list_rooms))
+                    .route("/rooms/ws", web::get().to(rooms_ws))
+                    .route("/stats", web::get().to(room_stats))
+                    .route("/quick-match", web::post().to(quick_match))
+                    .route("/solo", web::post().to(create_solo_game))
+                    .route("/bots", web::get().to(list_bots))
+                    .route("/bot-arena", web::post().to(create_bot_arena))
+                    .service(
+                        web::resource("/code-bots")
+                            .app_data(web::JsonConfig::default().limit(MAX_BOT_UPLOAD_BYTES))
+                            .route(web::get().to(list_code_bots))
+                            .route(web::post().to(upload_code_bot))
+                    )
+                    .route("/code-bots/{id}", web::get().to(get_code_bot))
+                    .route("/code-bots/{id}", web::delete().to(delete_code_bot))
+                    .route("/ws/{code}", web::get().to(snake_room_ws))
+                    .service(
+                        web::resource("/maps")
+                            .app_data(web::JsonConfig::default().limit(MAX_MAP_UPLOAD_BYTES))
+                            .route(web::get().to(list_maps))
+                            .route(web::post().to(upload_map))
+                    )
+                    .route("/maps/{id}", web::get().to(get_map))
+                    .route("/maps/{id}", web::delete().to(delete_map))
+                    .route("/replays/{id}", web::get().to(get_replay))
+                    .route("/replays/{id}/ws", web::get().to(replay_ws))
+                    .route("/gym", web::get().to(gym_spec))
+                    .route("/gym/envs", web::post().to(open_gym_env))
+                    .route("/gym/envs/{id}/reset", web::post().to(reset_gym_env))
+                    .route("/gym/envs/{id}/step", web::post().to(step_gym_env))
+                    .route("/gym/envs/{id}", web::delete().to(close_gym_env))
+                    .route("/gym/ws", web::get().to(gym_ws))
+            )
     })
     .bind((host.as_str(), port))?
     .run()